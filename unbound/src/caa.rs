@@ -0,0 +1,218 @@
+//! Certification Authority Authorization ([RFC 8659](https://tools.ietf.org/html/rfc8659)).
+use super::{Context, Result, Security};
+
+/// A CAA record.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Caa {
+    flags: u8,
+    tag: String,
+    value: Vec<u8>,
+}
+
+impl Caa {
+    /// Parse the data of a CAA record as returned by
+    /// [Answer::data](struct.Answer.html#method.data). Returns `None` if the
+    /// data is malformed.
+    pub fn parse(data: &[u8]) -> Option<Caa> {
+        let (&flags, rest) = data.split_first()?;
+        let (&len, rest) = rest.split_first()?;
+        let tag = rest.get(..len as usize)?;
+        if tag.is_empty() || !tag.iter().all(u8::is_ascii_alphanumeric) {
+            return None;
+        }
+        Some(Caa {
+            flags,
+            tag: String::from_utf8(tag.to_ascii_lowercase()).ok()?,
+            value: rest[len as usize..].to_vec(),
+        })
+    }
+    /// Returns the flags octet.
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+    /// Returns true if the Issuer Critical flag is set, meaning a CA that doesn't
+    /// understand the tag must not issue.
+    pub fn is_critical(&self) -> bool {
+        self.flags & 0x80 != 0
+    }
+    /// Returns the property tag in lowercase.
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+    /// Returns the raw property value.
+    pub fn value(&self) -> &[u8] {
+        &self.value
+    }
+    /// Returns the issuer domain name of an `issue` or `issuewild` property, or
+    /// `None` if the property is malformed. An empty name forbids issuance.
+    pub fn issuer(&self) -> Option<String> {
+        self.issue_value().map(|(issuer, _)| issuer)
+    }
+    /// Returns the parameters of an `issue` or `issuewild` property as key and
+    /// value pairs.
+    pub fn parameters(&self) -> Vec<(String, String)> {
+        self.issue_value()
+            .map(|(_, params)| params)
+            .unwrap_or_default()
+    }
+    // Splits an issue property value into the issuer domain name and parameters.
+    fn issue_value(&self) -> Option<(String, Vec<(String, String)>)> {
+        let value = std::str::from_utf8(&self.value).ok()?;
+        let mut parts = value.split(';');
+        let issuer = parts.next()?.trim();
+        let is_label =
+            |l: &str| !l.is_empty() && l.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+        if !issuer.is_empty() && !issuer.split('.').all(is_label) {
+            return None;
+        }
+        let mut params = Vec::new();
+        for param in parts.map(str::trim).filter(|p| !p.is_empty()) {
+            let mut kv = param.splitn(2, '=');
+            let key = kv.next()?.trim();
+            let value = kv.next()?.trim();
+            if key.is_empty() || !key.bytes().all(|b| b.is_ascii_alphanumeric()) {
+                return None;
+            }
+            params.push((key.to_owned(), value.to_owned()));
+        }
+        Some((issuer.to_ascii_lowercase(), params))
+    }
+}
+
+/// The outcome of a CAA check, as returned by
+/// [Context::check_caa](struct.Context.html#method.check_caa).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CaaCheck {
+    permitted: bool,
+    owner: Option<String>,
+    records: Vec<Caa>,
+    security: Security,
+}
+
+impl CaaCheck {
+    /// Returns true if the issuer may issue for the name.
+    pub fn permitted(&self) -> bool {
+        self.permitted
+    }
+    /// Returns the name the relevant RRset was found at, or `None` if no name up
+    /// to the top-level domain has CAA records.
+    pub fn owner(&self) -> Option<&str> {
+        self.owner.as_deref()
+    }
+    /// Returns the relevant RRset.
+    pub fn records(&self) -> &[Caa] {
+        &self.records
+    }
+    /// Returns the URLs of the `iodef` properties in the relevant RRset.
+    pub fn iodef(&self) -> Vec<String> {
+        self.records
+            .iter()
+            .filter(|r| r.tag == "iodef")
+            .filter_map(|r| String::from_utf8(r.value.clone()).ok())
+            .collect()
+    }
+    /// Returns the outcome of DNSSEC validation of the queries made, combined
+    /// as by [Security::downgrade](enum.Security.html#method.downgrade).
+    pub fn security(&self) -> &Security {
+        &self.security
+    }
+}
+
+// Returns true if the relevant RRset permits an issuer to issue.
+fn permits(records: &[Caa], issuer: &str, wildcard: bool) -> bool {
+    if records
+        .iter()
+        .any(|r| r.is_critical() && !["issue", "issuewild", "iodef"].contains(&&r.tag[..]))
+    {
+        return false;
+    }
+    let has_issuewild = records.iter().any(|r| r.tag == "issuewild");
+    let tag = if wildcard && has_issuewild {
+        "issuewild"
+    } else {
+        "issue"
+    };
+    let mut properties = records.iter().filter(|r| r.tag == tag).peekable();
+    if properties.peek().is_none() {
+        return true;
+    }
+    let issuer = issuer.trim_end_matches('.').to_ascii_lowercase();
+    properties.any(|r| r.issuer().is_some_and(|i| i == issuer))
+}
+
+impl Context {
+    /// Check whether an issuer, identified by its CAA issuer domain name, may
+    /// issue a certificate for a name. `wildcard` selects `issuewild` properties
+    /// for wildcard certificates.
+    ///
+    /// CAA records are looked up at the name and then at each parent domain
+    /// until a non-empty RRset is found. CNAMEs are followed by
+    /// [resolve](struct.Context.html#method.resolve), while the climb continues
+    /// from the parents of the original name. A failed lookup, including one that
+    /// fails DNSSEC validation, is an error, and issuance must not proceed.
+    pub fn check_caa(&self, name: &str, issuer: &str, wildcard: bool) -> Result<CaaCheck> {
+        let mut name = name.trim_end_matches('.');
+        let mut security = Security::Secure;
+        while !name.is_empty() {
+            let answer = self.resolve(name, 257, 1)?.checked()?;
+            security.downgrade(answer.security());
+            let records: Vec<_> = answer.data().filter_map(Caa::parse).collect();
+            if !records.is_empty() {
+                return Ok(CaaCheck {
+                    permitted: permits(&records, issuer, wildcard),
+                    owner: Some(name.to_owned() + "."),
+                    records,
+                    security,
+                });
+            }
+            name = name.find('.').map_or("", |i| &name[i + 1..]);
+        }
+        Ok(CaaCheck {
+            permitted: true,
+            owner: None,
+            records: Vec::new(),
+            security,
+        })
+    }
+}
+
+#[test]
+fn test_check_caa() {
+    let ctx = Context::new().unwrap();
+    ctx.zone_add("caa.test.", "static").unwrap();
+    ctx.data_add("caa.test. CAA 0 issue \"ca.example; account=1\"")
+        .unwrap();
+    ctx.data_add("caa.test. CAA 0 issuewild \";\"").unwrap();
+    ctx.data_add("caa.test. CAA 0 iodef \"mailto:caa@caa.test\"")
+        .unwrap();
+    ctx.data_add("crit.caa.test. CAA 128 future \"x\"").unwrap();
+    ctx.data_add("crit.caa.test. CAA 0 issue \"ca.example\"")
+        .unwrap();
+    let check = ctx
+        .check_caa("www.sub.caa.test", "CA.example.", false)
+        .unwrap();
+    assert!(check.permitted());
+    assert_eq!(check.owner(), Some("caa.test."));
+    assert_eq!(check.iodef(), ["mailto:caa@caa.test"]);
+    assert_eq!(
+        check
+            .records()
+            .iter()
+            .find(|r| r.tag() == "issue")
+            .unwrap()
+            .parameters(),
+        [("account".to_owned(), "1".to_owned())]
+    );
+    assert!(!ctx
+        .check_caa("caa.test", "ca.example", true)
+        .unwrap()
+        .permitted());
+    assert!(!ctx
+        .check_caa("caa.test", "other.example", false)
+        .unwrap()
+        .permitted());
+    assert!(!ctx
+        .check_caa("crit.caa.test", "ca.example", false)
+        .unwrap()
+        .permitted());
+}
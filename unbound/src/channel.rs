@@ -0,0 +1,203 @@
+//! Channel based asynchronous resolution.
+//!
+//! Results are delivered when the `Context` is driven by `process` or `wait` as
+//! with `resolve_async`.
+use std::sync::mpsc;
+
+use super::{Answer, AsyncID, Context, Error, Result};
+
+#[cfg(feature = "crossbeam-channel")]
+use crossbeam_channel;
+
+/// A question submitted to a [Context](struct.Context.html) via `resolve_into` or
+/// `resolve_many`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Query {
+    /// Name being queried.
+    pub name: String,
+    /// Type being queried.
+    pub rrtype: u16,
+    /// Class being queried.
+    pub class: u16,
+}
+
+impl Query {
+    /// Create a new `Query`.
+    pub fn new<S: Into<String>>(name: S, rrtype: u16, class: u16) -> Query {
+        Query {
+            name: name.into(),
+            rrtype,
+            class,
+        }
+    }
+}
+
+impl<'a> From<(&'a str, u16, u16)> for Query {
+    fn from((name, rrtype, class): (&'a str, u16, u16)) -> Query {
+        Query::new(name, rrtype, class)
+    }
+}
+
+impl From<(String, u16, u16)> for Query {
+    fn from((name, rrtype, class): (String, u16, u16)) -> Query {
+        Query::new(name, rrtype, class)
+    }
+}
+
+/// The sending half of a channel that query results can be delivered to.
+///
+/// Results are sent from within `process` or `wait`. A send that would block,
+/// such as on a full bounded channel, blocks the thread driving the `Context`.
+/// Results sent after the receiving half is dropped are discarded.
+pub trait ResultSender: 'static {
+    /// Deliver the result of `query`.
+    fn send_result(&self, query: Query, result: Result<Answer>);
+}
+
+impl ResultSender for mpsc::Sender<(Query, Result<Answer>)> {
+    fn send_result(&self, query: Query, result: Result<Answer>) {
+        let _ = self.send((query, result));
+    }
+}
+
+impl ResultSender for mpsc::SyncSender<(Query, Result<Answer>)> {
+    fn send_result(&self, query: Query, result: Result<Answer>) {
+        let _ = self.send((query, result));
+    }
+}
+
+#[cfg(feature = "crossbeam-channel")]
+impl ResultSender for crossbeam_channel::Sender<(Query, Result<Answer>)> {
+    fn send_result(&self, query: Query, result: Result<Answer>) {
+        let _ = self.send((query, result));
+    }
+}
+
+impl Context {
+    /// Resolve and validate a query asynchronously, sending the result to `sender`.
+    /// Cancel the query by supplying the `AsyncID` to `cancel`.
+    pub fn resolve_into<S>(&self, name: &str, rrtype: u16, class: u16, sender: S) -> Result<AsyncID>
+    where
+        S: ResultSender,
+    {
+        let query = Query::new(name, rrtype, class);
+        self.resolve_async(name, rrtype, class, move |_, result| {
+            sender.send_result(query.clone(), result)
+        })
+    }
+    /// Resolve and validate a batch of queries asynchronously. Results are
+    /// received in the order they complete.
+    pub fn resolve_many<I>(&self, queries: I) -> Result<mpsc::Receiver<(Query, Result<Answer>)>>
+    where
+        I: IntoIterator,
+        I::Item: Into<Query>,
+    {
+        let (tx, rx) = mpsc::channel();
+        self.resolve_many_into(queries, tx)?;
+        Ok(rx)
+    }
+    /// Resolve and validate a batch of queries asynchronously, sending each result
+    /// to a clone of `sender`. If a query cannot be submitted those already
+    /// submitted are cancelled.
+    pub fn resolve_many_into<I, S>(&self, queries: I, sender: S) -> Result<Vec<AsyncID>>
+    where
+        I: IntoIterator,
+        I::Item: Into<Query>,
+        S: ResultSender + Clone,
+    {
+        let mut ids = Vec::new();
+        for query in queries {
+            let query = query.into();
+            match self.resolve_into(&query.name, query.rrtype, query.class, sender.clone()) {
+                Ok(id) => ids.push(id),
+                Err(err) => {
+                    for id in ids {
+                        self.cancel(id);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Ok(ids)
+    }
+    /// Resolve a batch of queries asynchronously and process the `Context` until
+    /// every result is available. Results are returned in the order of `queries`;
+    /// queries not answered within the lookup timeout fail with
+    /// `Error::TimedOut`.
+    pub(crate) fn resolve_all(&self, queries: &[(&str, u16, u16)]) -> Result<Vec<Result<Answer>>> {
+        let (tx, rx) = mpsc::channel();
+        let deadline = self.lookup_deadline();
+        let mut ids = Vec::with_capacity(queries.len());
+        for (i, &(name, rrtype, class)) in queries.iter().enumerate() {
+            let tx = tx.clone();
+            let submitted = self.resolve_async_with_deadline(
+                name,
+                rrtype,
+                class,
+                deadline,
+                move |_, result| {
+                    let _ = tx.send((i, result));
+                },
+            );
+            match submitted {
+                Ok(id) => ids.push(id),
+                Err(err) => {
+                    for id in ids {
+                        self.cancel(id);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        drop(tx);
+        let mut results: Vec<Option<Result<Answer>>> = queries.iter().map(|_| None).collect();
+        let mut remaining = queries.len();
+        self.process_until(|| loop {
+            match rx.try_recv() {
+                Ok((i, result)) => {
+                    results[i] = Some(result);
+                    remaining -= 1;
+                }
+                Err(mpsc::TryRecvError::Empty) if remaining > 0 => return None,
+                // Callbacks discarded without being called drop their sender.
+                Err(_) => return Some(()),
+            }
+        })?;
+        Ok(results
+            .into_iter()
+            .map(|r| r.unwrap_or(Err(Error::Cancelled)))
+            .collect())
+    }
+}
+
+#[test]
+fn test_resolve_many() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    let rx = ctx
+        .resolve_many(vec![("localhost", 1, 1), ("localhost", 28, 1)])
+        .unwrap();
+    ctx.wait().unwrap();
+    let mut types: Vec<_> = rx
+        .try_iter()
+        .map(|(q, r)| {
+            assert_eq!(r.unwrap().qtype(), q.rrtype);
+            q.rrtype
+        })
+        .collect();
+    types.sort();
+    assert_eq!(types, [1, 28]);
+}
+
+#[test]
+#[cfg(feature = "crossbeam-channel")]
+fn test_resolve_into_crossbeam() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    let (tx, rx) = crossbeam_channel::unbounded();
+    ctx.resolve_into("localhost", 1, 1, tx).unwrap();
+    ctx.wait().unwrap();
+    let (query, result) = rx.try_recv().unwrap();
+    assert_eq!(query, Query::new("localhost", 1, 1));
+    assert!(result.unwrap().havedata());
+}
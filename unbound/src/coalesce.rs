@@ -0,0 +1,215 @@
+//! Coalescing of identical in-flight queries.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use super::{Answer, AsyncID, Context, Result};
+
+type Waiter = Box<dyn Fn(AsyncID, Result<Arc<Answer>>) + Send + 'static>;
+type Inflight = Arc<Mutex<HashMap<Key, Arc<Mutex<Entry>>>>>;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Key {
+    name: String,
+    rrtype: u16,
+    class: u16,
+}
+
+#[derive(Default)]
+struct Entry {
+    id: Option<AsyncID>,
+    waiters: Vec<Waiter>,
+    // Set once the waiters have been called or the query couldn't be made.
+    done: bool,
+}
+
+// Removes `entry` from the map if it is still the entry for `key`.
+fn remove(inflight: &Inflight, key: &Key, entry: &Arc<Mutex<Entry>>) {
+    let mut inflight = inflight.lock().expect("remove acquire inflight");
+    if inflight.get(key).is_some_and(|e| Arc::ptr_eq(e, entry)) {
+        inflight.remove(key);
+    }
+}
+
+/// Deduplicates identical queries made with `resolve_async`.
+///
+/// While a query for a given name, type and class is outstanding, further
+/// requests for the same question are attached to it rather than creating a new
+/// libunbound query. The single [Answer](struct.Answer.html) is shared among
+/// every waiter.
+///
+/// Names are compared case-insensitively and without regard to a trailing dot.
+/// Cancelling the `AsyncID` returned by `resolve_async` cancels the query for
+/// every waiter.
+#[derive(Clone, Default)]
+pub struct Coalescer {
+    inflight: Inflight,
+}
+
+impl Coalescer {
+    /// Create a new `Coalescer`.
+    pub fn new() -> Coalescer {
+        Default::default()
+    }
+    /// Resolve and validate a query asynchronously with `ctx`, sharing the result
+    /// with any identical query already in flight.
+    pub fn resolve_async<C>(
+        &self,
+        ctx: &Context,
+        name: &str,
+        rrtype: u16,
+        class: u16,
+        callback: C,
+    ) -> Result<AsyncID>
+    where
+        C: Fn(AsyncID, Result<Arc<Answer>>) + Send + 'static,
+    {
+        let key = Key {
+            name: name.trim_end_matches('.').to_ascii_lowercase(),
+            rrtype,
+            class,
+        };
+        // The map lock is never held while waiting for an entry or calling into
+        // `ctx`, whose callbacks take the map lock.
+        loop {
+            let mut inflight = self
+                .inflight
+                .lock()
+                .expect("resolve_async acquire inflight");
+            let existing = match inflight.get(&key) {
+                Some(entry) => entry.clone(),
+                None => {
+                    let entry = Arc::new(Mutex::new(Entry::default()));
+                    inflight.insert(key.clone(), entry.clone());
+                    // The new entry stays locked until its id is set, so anyone
+                    // finding it in the meantime waits.
+                    let mut locked = entry.lock().expect("resolve_async acquire entry");
+                    drop(inflight);
+                    locked.waiters.push(Box::new(callback));
+                    return self.submit(ctx, key, &entry, locked);
+                }
+            };
+            drop(inflight);
+            let mut entry = existing.lock().expect("resolve_async acquire entry");
+            if !entry.done {
+                entry.waiters.push(Box::new(callback));
+                return Ok(entry.id.expect("in flight entry has id"));
+            }
+            // The entry completed after it was found and is leaving the map, so
+            // look again.
+        }
+    }
+    // Makes the query for a new entry, which the caller has locked.
+    fn submit(
+        &self,
+        ctx: &Context,
+        key: Key,
+        entry: &Arc<Mutex<Entry>>,
+        mut locked: MutexGuard<Entry>,
+    ) -> Result<AsyncID> {
+        let shared = entry.clone();
+        let map = self.inflight.clone();
+        let done_key = key.clone();
+        let submitted = ctx.resolve_async(&key.name, key.rrtype, key.class, move |id, result| {
+            // The entry leaves the map before its waiters are taken, so no
+            // waiter can be attached once they have been called.
+            remove(&map, &done_key, &shared);
+            let waiters = {
+                let mut entry = shared.lock().expect("callback acquire entry");
+                entry.done = true;
+                std::mem::take(&mut entry.waiters)
+            };
+            let result = result.map(Arc::new);
+            for waiter in waiters {
+                waiter(id, result.clone());
+            }
+        });
+        match submitted {
+            Ok(id) => {
+                locked.id = Some(id);
+                Ok(id)
+            }
+            Err(err) => {
+                locked.done = true;
+                locked.waiters.clear();
+                drop(locked);
+                remove(&self.inflight, &key, entry);
+                Err(err)
+            }
+        }
+    }
+    /// Returns the number of distinct queries in flight.
+    pub fn in_flight(&self) -> usize {
+        self.inflight
+            .lock()
+            .expect("in_flight acquire inflight")
+            .len()
+    }
+}
+
+impl std::fmt::Debug for Coalescer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_fmt(format_args!("Coalescer({:p}/{})", self, self.in_flight()))
+    }
+}
+
+#[test]
+fn test_coalesce() {
+    use std::sync::mpsc;
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    let coalescer = Coalescer::new();
+    let (tx, rx) = mpsc::channel();
+    let mut ids = Vec::new();
+    for name in &["localhost", "LOCALHOST.", "localhost"] {
+        let tx = tx.clone();
+        let id = coalescer
+            .resolve_async(&ctx, name, 1, 1, move |_, r| tx.send(r.unwrap()).unwrap())
+            .unwrap();
+        ids.push(id);
+    }
+    assert_eq!(coalescer.in_flight(), 1);
+    assert!(ids.iter().all(|id| *id == ids[0]));
+    ctx.wait().unwrap();
+    let answers: Vec<_> = rx.try_iter().collect();
+    assert_eq!(answers.len(), 3);
+    assert!(answers.iter().all(|a| Arc::ptr_eq(a, &answers[0])));
+    assert_eq!(coalescer.in_flight(), 0);
+}
+
+#[test]
+fn test_coalesce_threads() {
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    let ctx = Arc::new(Context::new().unwrap());
+    ctx.async_via_thread().unwrap();
+    let coalescer = Coalescer::new();
+    let (tx, rx) = mpsc::channel();
+    // Submit from several threads while this one processes results.
+    let submitters: Vec<_> = (0..4)
+        .map(|_| {
+            let (ctx, coalescer, tx) = (ctx.clone(), coalescer.clone(), tx.clone());
+            thread::spawn(move || {
+                for i in 0..250 {
+                    let tx = tx.clone();
+                    let rrtype = if i % 2 == 0 { 1 } else { 28 };
+                    coalescer
+                        .resolve_async(&ctx, "localhost", rrtype, 1, move |_, r| {
+                            tx.send(r.is_ok()).unwrap()
+                        })
+                        .unwrap();
+                }
+            })
+        })
+        .collect();
+    drop(tx);
+    let mut answered = 0;
+    while answered < 1000 {
+        ctx.process_timeout(Duration::from_millis(10)).unwrap();
+        answered += rx.try_iter().filter(|&ok| ok).count();
+    }
+    for submitter in submitters {
+        submitter.join().unwrap();
+    }
+    assert_eq!(coalescer.in_flight(), 0);
+}
@@ -0,0 +1,359 @@
+//! DNS-Based Authentication of Named Entities
+//! ([RFC 6698](https://tools.ietf.org/html/rfc6698),
+//! [RFC 7671](https://tools.ietf.org/html/rfc7671)).
+use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use openssl::hash::{hash, MessageDigest};
+use openssl::ssl::{SslRef, SslVerifyMode};
+use openssl::x509::{X509Ref, X509VerifyResult, X509};
+
+use super::{Context, Error, Result, Security};
+
+/// Certificate usage PKIX-TA: the CA certificate or key must be in a chain that
+/// passes PKIX validation.
+pub const USAGE_PKIX_TA: u8 = 0;
+/// Certificate usage PKIX-EE: the end-entity certificate or key must match and
+/// pass PKIX validation.
+pub const USAGE_PKIX_EE: u8 = 1;
+/// Certificate usage DANE-TA: the trust anchor certificate or key must be in
+/// the chain, which need not pass PKIX validation.
+pub const USAGE_DANE_TA: u8 = 2;
+/// Certificate usage DANE-EE: the end-entity certificate or key must match.
+pub const USAGE_DANE_EE: u8 = 3;
+
+/// A TLSA record.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Tlsa {
+    usage: u8,
+    selector: u8,
+    matching_type: u8,
+    data: Vec<u8>,
+}
+
+impl Tlsa {
+    /// Create a TLSA record.
+    pub fn new(usage: u8, selector: u8, matching_type: u8, data: Vec<u8>) -> Tlsa {
+        Tlsa {
+            usage,
+            selector,
+            matching_type,
+            data,
+        }
+    }
+    /// Parse the data of a TLSA record as returned by
+    /// [Answer::data](struct.Answer.html#method.data). Returns `None` if the
+    /// data is malformed.
+    pub fn parse(data: &[u8]) -> Option<Tlsa> {
+        match *data {
+            [usage, selector, matching_type, ref rest @ ..] => Some(Tlsa {
+                usage,
+                selector,
+                matching_type,
+                data: rest.to_vec(),
+            }),
+            _ => None,
+        }
+    }
+    /// Returns the certificate usage.
+    pub fn usage(&self) -> u8 {
+        self.usage
+    }
+    /// Returns the selector: 0 for the full certificate, 1 for the
+    /// SubjectPublicKeyInfo.
+    pub fn selector(&self) -> u8 {
+        self.selector
+    }
+    /// Returns the matching type: 0 for exact match, 1 for SHA-256, 2 for
+    /// SHA-512.
+    pub fn matching_type(&self) -> u8 {
+        self.matching_type
+    }
+    /// Returns the certificate association data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+    // Returns true if the record's parameters are understood.
+    fn is_usable(&self) -> bool {
+        self.usage <= USAGE_DANE_EE && self.selector <= 1 && self.matching_type <= 2
+    }
+    // Returns true if the record matches the certificate.
+    fn matches(&self, cert: &X509Ref) -> bool {
+        let selected = match self.selector {
+            0 => cert.to_der(),
+            _ => cert.public_key().and_then(|key| key.public_key_to_der()),
+        };
+        let selected = match selected {
+            Ok(selected) => selected,
+            Err(_) => return false,
+        };
+        match self.matching_type {
+            0 => selected == self.data,
+            1 => hash(MessageDigest::sha256(), &selected).is_ok_and(|d| *d == *self.data),
+            _ => hash(MessageDigest::sha512(), &selected).is_ok_and(|d| *d == *self.data),
+        }
+    }
+}
+
+// Returns true if each certificate in the chain up to `end` was issued and
+// signed by the next.
+fn chains_to(chain: &[X509], end: usize) -> bool {
+    chain[..=end].windows(2).all(|pair| {
+        pair[1].issued(&pair[0]) == X509VerifyResult::OK
+            && pair[1]
+                .public_key()
+                .and_then(|key| pair[0].verify(&key))
+                .unwrap_or(false)
+    })
+}
+
+// Returns the positions of the certificates in the chain that match a DANE-TA
+// record and issued the chain.
+pub(crate) fn dane_ta_anchors<'a>(
+    records: &'a [Tlsa],
+    chain: &'a [X509],
+) -> impl Iterator<Item = usize> + 'a {
+    (1..chain.len()).filter(move |&i| {
+        records
+            .iter()
+            .any(|r| r.is_usable() && r.usage == USAGE_DANE_TA && r.matches(&chain[i]))
+            && chains_to(chain, i)
+    })
+}
+
+/// Verify a certificate chain against a set of TLSA records. The chain starts
+/// with the end-entity certificate. `pkix_valid` indicates whether the chain
+/// passed PKIX validation, which records with PKIX usages require.
+///
+/// Returns true if any usable record matches. Records with unknown parameters
+/// are ignored. Callers are responsible for checking the certificate's names
+/// for all usages other than DANE-EE.
+pub fn verify_tlsa(records: &[Tlsa], chain: &[X509], pkix_valid: bool) -> bool {
+    let ee = match chain.first() {
+        Some(ee) => ee,
+        None => return false,
+    };
+    records
+        .iter()
+        .filter(|r| r.is_usable())
+        .any(|r| match r.usage {
+            USAGE_PKIX_TA => pkix_valid && chain[1..].iter().any(|cert| r.matches(cert)),
+            USAGE_PKIX_EE => pkix_valid && r.matches(ee),
+            USAGE_DANE_TA => dane_ta_anchors(slice::from_ref(r), chain).next().is_some(),
+            _ => r.matches(ee),
+        })
+}
+
+/// Verify the peer of an `SslRef` against a set of TLSA records with
+/// [verify_tlsa](fn.verify_tlsa.html) in place of OpenSSL's verification. PKIX
+/// validation, including any host name check configured on the `SslRef`, is
+/// still performed for records with PKIX usages. DANE-TA records also require
+/// the certificates to be within their validity periods and the host name, if
+/// configured, to match; DANE-EE records require neither.
+pub fn set_dane_verify(ssl: &mut SslRef, records: Vec<Tlsa>) {
+    let pkix_valid = AtomicBool::new(true);
+    let name_or_validity_error = AtomicBool::new(false);
+    ssl.set_verify_callback(SslVerifyMode::PEER, move |preverify_ok, store| {
+        if !preverify_ok {
+            pkix_valid.store(false, Ordering::SeqCst);
+            if is_name_or_validity_error(store.error()) {
+                name_or_validity_error.store(true, Ordering::SeqCst);
+            }
+        }
+        if store.error_depth() != 0 {
+            return true;
+        }
+        // Errors found later at depth 0 are reported in further calls, the last
+        // of which has the final say.
+        let chain: Vec<X509> = match store.chain() {
+            Some(chain) => chain.iter().map(X509Ref::to_owned).collect(),
+            None => return false,
+        };
+        if name_or_validity_error.load(Ordering::SeqCst) {
+            let dane_ee: Vec<Tlsa> = records
+                .iter()
+                .filter(|r| r.usage == USAGE_DANE_EE)
+                .cloned()
+                .collect();
+            return verify_tlsa(&dane_ee, &chain, false);
+        }
+        verify_tlsa(&records, &chain, pkix_valid.load(Ordering::SeqCst))
+    });
+}
+
+// Whether an OpenSSL verification error is a certificate outside its validity
+// period or a name mismatch, rather than a chain that isn't trusted.
+fn is_name_or_validity_error(error: X509VerifyResult) -> bool {
+    match error.as_raw() {
+        // X509_V_ERR_CERT_NOT_YET_VALID, X509_V_ERR_CERT_HAS_EXPIRED and errors
+        // in the notBefore and notAfter fields.
+        9 | 10 | 13 | 14 => true,
+        // X509_V_ERR_HOSTNAME_MISMATCH, X509_V_ERR_EMAIL_MISMATCH and
+        // X509_V_ERR_IP_ADDRESS_MISMATCH.
+        62..=64 => true,
+        _ => false,
+    }
+}
+
+impl Context {
+    /// Look up the TLSA records of a service, querying
+    /// `_port._proto.host`.
+    ///
+    /// Records are only returned from secure answers. An insecure answer yields
+    /// no records, as its records are unusable
+    /// ([RFC 6698 section 4.1](https://tools.ietf.org/html/rfc6698#section-4.1)),
+    /// while a bogus answer is an `Error::Bogus`.
+    pub fn lookup_tlsa(&self, port: u16, proto: &str, host: &str) -> Result<Vec<Tlsa>> {
+        let proto = proto.trim_start_matches('_');
+        let answer = self.resolve(&format!("_{}._{}.{}", port, proto, host), 52, 1)?;
+        if answer.bogus() {
+            return Err(Error::Bogus);
+        }
+        let answer = answer.checked()?;
+        match answer.security() {
+            Security::Secure => Ok(answer.data().filter_map(Tlsa::parse).collect()),
+            Security::Insecure => Ok(Vec::new()),
+            _ => Err(Error::Bogus),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn fixture_chain(
+    name: &str,
+) -> (X509, X509, openssl::pkey::PKey<openssl::pkey::Private>) {
+    use openssl::asn1::Asn1Time;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::nid::Nid;
+    use openssl::pkey::PKey;
+    use openssl::x509::extension::{BasicConstraints, SubjectAlternativeName};
+    use openssl::x509::X509NameBuilder;
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+    let key = || PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+    let cert = |cn: &str, key: &PKey<_>, issuer: Option<(&X509, &PKey<_>)>, ca: bool| {
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_text("CN", cn).unwrap();
+        let subject = subject.build();
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&subject).unwrap();
+        builder.set_pubkey(key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::days_from_now(0).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::days_from_now(if ca { 30 } else { 1 }).unwrap())
+            .unwrap();
+        if ca {
+            let bc = BasicConstraints::new().critical().ca().build().unwrap();
+            builder.append_extension(bc).unwrap();
+        } else {
+            let san = SubjectAlternativeName::new()
+                .dns(cn)
+                .build(&builder.x509v3_context(issuer.map(|i| &**i.0), None))
+                .unwrap();
+            builder.append_extension(san).unwrap();
+        }
+        let (issuer_name, signer) = match issuer {
+            Some((cert, key)) => (cert.subject_name().to_owned().unwrap(), key),
+            None => (subject.to_owned().unwrap(), key),
+        };
+        builder.set_issuer_name(&issuer_name).unwrap();
+        builder.sign(signer, MessageDigest::sha256()).unwrap();
+        builder.build()
+    };
+    let ca_key = key();
+    let ca = cert("Test CA", &ca_key, None, true);
+    let ee_key = key();
+    let ee = cert(name, &ee_key, Some((&ca, &ca_key)), false);
+    (ee, ca, ee_key)
+}
+
+#[test]
+fn test_verify_tlsa() {
+    let (ee, ca, _) = fixture_chain("dane.test");
+    let spki_sha256 = |cert: &X509| {
+        let spki = cert.public_key().unwrap().public_key_to_der().unwrap();
+        hash(MessageDigest::sha256(), &spki).unwrap().to_vec()
+    };
+    let chain = [ee.clone(), ca.clone()];
+    let dane_ee = [Tlsa::new(USAGE_DANE_EE, 1, 1, spki_sha256(&ee))];
+    assert!(verify_tlsa(&dane_ee, &chain, false));
+    let dane_ta = [Tlsa::new(USAGE_DANE_TA, 0, 0, ca.to_der().unwrap())];
+    assert!(verify_tlsa(&dane_ta, &chain, false));
+    // The trust anchor must have issued the chain.
+    let (_, other_ca, _) = fixture_chain("dane.test");
+    assert!(!verify_tlsa(&dane_ta, &[ee.clone(), other_ca], false));
+    let pkix_ee = [Tlsa::new(USAGE_PKIX_EE, 1, 1, spki_sha256(&ee))];
+    assert!(!verify_tlsa(&pkix_ee, &chain, false));
+    assert!(verify_tlsa(&pkix_ee, &chain, true));
+    let wrong = Tlsa::new(USAGE_DANE_EE, 1, 1, spki_sha256(&ca));
+    assert!(!verify_tlsa(&[wrong], &chain, true));
+    let unknown = Tlsa::new(4, 1, 1, spki_sha256(&ee));
+    assert!(!verify_tlsa(&[unknown], &chain, true));
+    assert_eq!(
+        Tlsa::parse(&[3, 1, 1, 0xAB]),
+        Some(Tlsa::new(3, 1, 1, vec![0xAB]))
+    );
+    assert_eq!(Tlsa::parse(&[3, 1]), None);
+}
+
+#[test]
+fn test_set_dane_verify() {
+    use openssl::ssl::{SslAcceptor, SslConnector, SslMethod};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+    let (ee, ca, ee_key) = fixture_chain("dane.test");
+    let spki = ee.public_key().unwrap().public_key_to_der().unwrap();
+    let digest = hash(MessageDigest::sha256(), &spki).unwrap().to_vec();
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_private_key(&ee_key).unwrap();
+    acceptor.set_certificate(&ee).unwrap();
+    let ca_der = ca.to_der().unwrap();
+    acceptor.add_extra_chain_cert(ca).unwrap();
+    let acceptor = acceptor.build();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        for stream in listener.incoming().take(5) {
+            let _ = acceptor.accept(stream.unwrap());
+        }
+    });
+    let connector = SslConnector::builder(SslMethod::tls()).unwrap().build();
+    let connect = |host: &str, records: Vec<Tlsa>| {
+        let mut ssl = connector.configure().unwrap().into_ssl(host).unwrap();
+        set_dane_verify(&mut ssl, records);
+        let stream = TcpStream::connect(addr).unwrap();
+        ssl.connect(stream).is_ok()
+    };
+    let dane_ee = Tlsa::new(USAGE_DANE_EE, 1, 1, digest.clone());
+    let dane_ta = Tlsa::new(USAGE_DANE_TA, 0, 0, ca_der);
+    assert!(connect("dane.test", vec![dane_ee.clone()]));
+    assert!(!connect(
+        "dane.test",
+        vec![Tlsa::new(USAGE_PKIX_EE, 1, 1, digest)]
+    ));
+    assert!(connect("dane.test", vec![dane_ta.clone()]));
+    // DANE-TA requires the name to match, DANE-EE doesn't.
+    assert!(!connect("other.test", vec![dane_ta.clone()]));
+    assert!(connect("other.test", vec![dane_ta, dane_ee]));
+    server.join().unwrap();
+}
+
+#[test]
+fn test_lookup_tlsa() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    ctx.zone_add("dane.test.", "static").unwrap();
+    ctx.data_add("_443._tcp.dane.test. TLSA 3 1 1 ABCD")
+        .unwrap();
+    // Local data is insecure, so its records aren't usable.
+    assert_eq!(ctx.lookup_tlsa(443, "tcp", "dane.test").unwrap(), []);
+    // The stand-in server doesn't prove that names don't exist, so a missing
+    // name is bogus.
+    let ctx = super::signed_zone::SignedZone::new("dane.example.").context();
+    assert_eq!(
+        ctx.lookup_tlsa(443, "tcp", "missing.dane.example"),
+        Err(Error::Bogus)
+    );
+}
@@ -0,0 +1,166 @@
+//! DKIM public key records ([RFC 6376](https://tools.ietf.org/html/rfc6376)).
+use openssl::base64;
+
+use super::wire;
+use super::{Context, Result, Security};
+
+// Splits a colon separated list.
+fn colon_list(value: &str) -> Vec<String> {
+    value
+        .split(':')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// A DKIM public key record.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DkimKey {
+    hash_algorithms: Vec<String>,
+    key_type: String,
+    notes: String,
+    public_key: Vec<u8>,
+    service_types: Vec<String>,
+    flags: Vec<String>,
+}
+
+impl DkimKey {
+    /// Parse the text of a DKIM key record. Returns `None` if the record is
+    /// malformed or has no `p=` tag.
+    pub fn parse(text: &str) -> Option<DkimKey> {
+        let mut key = DkimKey {
+            hash_algorithms: Vec::new(),
+            key_type: "rsa".to_owned(),
+            notes: String::new(),
+            public_key: Vec::new(),
+            service_types: vec!["*".to_owned()],
+            flags: Vec::new(),
+        };
+        let mut public_key = None;
+        for (i, &(name, value)) in wire::parse_tags(text)?.iter().enumerate() {
+            match name {
+                "v" if i == 0 && value == "DKIM1" => {}
+                "v" => return None,
+                "h" => key.hash_algorithms = colon_list(value),
+                "k" => key.key_type = value.to_owned(),
+                "n" => key.notes = value.to_owned(),
+                "p" => {
+                    let value: String = value.split_whitespace().collect();
+                    public_key = Some(if value.is_empty() {
+                        Vec::new()
+                    } else {
+                        base64::decode_block(&value).ok()?
+                    });
+                }
+                "s" => key.service_types = colon_list(value),
+                "t" => key.flags = colon_list(value),
+                _ => {}
+            }
+        }
+        key.public_key = public_key?;
+        Some(key)
+    }
+    /// Returns the hash algorithms the key may be used with. Empty if any are
+    /// allowed.
+    pub fn hash_algorithms(&self) -> &[String] {
+        &self.hash_algorithms
+    }
+    /// Returns the key type, `rsa` unless stated otherwise.
+    pub fn key_type(&self) -> &str {
+        &self.key_type
+    }
+    /// Returns the notes intended for administrators.
+    pub fn notes(&self) -> &str {
+        &self.notes
+    }
+    /// Returns the public key data, a DER encoded SubjectPublicKeyInfo for RSA
+    /// keys. Empty if the key has been revoked.
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+    /// Returns the service types the key applies to.
+    pub fn service_types(&self) -> &[String] {
+        &self.service_types
+    }
+    /// Returns the flags in the `t=` tag.
+    pub fn flags(&self) -> &[String] {
+        &self.flags
+    }
+    /// Returns true if the key has been revoked.
+    pub fn is_revoked(&self) -> bool {
+        self.public_key.is_empty()
+    }
+    /// Returns true if the domain is testing DKIM (`t=y`).
+    pub fn is_testing(&self) -> bool {
+        self.flags.iter().any(|f| f == "y")
+    }
+    /// Returns true if the signing domain must match the `i=` domain exactly
+    /// (`t=s`).
+    pub fn is_strict(&self) -> bool {
+        self.flags.iter().any(|f| f == "s")
+    }
+}
+
+/// A DKIM key lookup, as returned by
+/// [Context::lookup_dkim_key](struct.Context.html#method.lookup_dkim_key).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupDkim {
+    key: Option<DkimKey>,
+    security: Security,
+}
+
+impl LookupDkim {
+    /// Returns the first valid key record, if any.
+    pub fn key(&self) -> Option<&DkimKey> {
+        self.key.as_ref()
+    }
+    /// Returns the outcome of DNSSEC validation of the TXT query.
+    pub fn security(&self) -> &Security {
+        &self.security
+    }
+}
+
+impl Context {
+    /// Look up the DKIM public key of a selector at `selector._domainkey.domain`.
+    pub fn lookup_dkim_key(&self, selector: &str, domain: &str) -> Result<LookupDkim> {
+        let name = format!("{}._domainkey.{}", selector, domain.trim_end_matches('.'));
+        let answer = self.resolve(&name, 16, 1)?.checked()?;
+        Ok(LookupDkim {
+            key: wire::answer_txt(&answer)
+                .iter()
+                .find_map(|text| DkimKey::parse(text)),
+            security: answer.security(),
+        })
+    }
+}
+
+#[test]
+fn test_lookup_dkim_key() {
+    let ctx = Context::new().unwrap();
+    ctx.zone_add("dkim.test.", "static").unwrap();
+    // The key from RFC 6376, appendix C, split across character-strings.
+    ctx.data_add(concat!(
+        "brisbane._domainkey.dkim.test. TXT \"v=DKIM1; p=MIGfMA0GCSqGSIb3DQEBAQUAA4GNADCBiQKBgQDwIRP/UC3SBsEmGqZ9ZJW3/DkMoGeLnQg1fWn7/zYt\" ",
+        "\"IxN2SnFCjxOCKG9v3b4jYfcTNh5ijSsq631uBItLa7od+v/RtdC2UzJ1lWT947qR+Rcac2gbto/NMqJ0fzfVjH4OuKhi\" ",
+        "\"tdY9tf6mcwGjaNBcWToIMmPSPDdQPNUYckcQ2QIDAQAB; t=y:s\"",
+    ))
+    .unwrap();
+    ctx.data_add("old._domainkey.dkim.test. TXT \"v=DKIM1; k=rsa; p=\"")
+        .unwrap();
+    let lookup = ctx.lookup_dkim_key("brisbane", "dkim.test").unwrap();
+    let key = lookup.key().unwrap();
+    assert_eq!(key.key_type(), "rsa");
+    assert_eq!(key.public_key().len(), 162);
+    assert!(key.is_testing() && key.is_strict());
+    assert!(!lookup.security().is_secure());
+    let lookup = ctx.lookup_dkim_key("old", "dkim.test.").unwrap();
+    assert!(lookup.key().unwrap().is_revoked());
+    assert_eq!(
+        ctx.lookup_dkim_key("none", "dkim.test").unwrap().key(),
+        None
+    );
+    assert_eq!(DkimKey::parse("v=DKIM1; k=rsa"), None);
+    assert_eq!(DkimKey::parse("p=; p="), None);
+    assert_eq!(DkimKey::parse("k=rsa; v=DKIM1; p="), None);
+}
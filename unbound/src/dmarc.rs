@@ -0,0 +1,323 @@
+//! DMARC policy records ([RFC 7489](https://tools.ietf.org/html/rfc7489)).
+use super::wire;
+use super::{Context, Result, Security};
+
+// The most labels of a domain whose DMARC record is looked up, after the first.
+const MAX_WALK_LABELS: usize = 7;
+
+/// The handling a DMARC policy requests for mail that fails DMARC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DmarcPolicy {
+    /// Take no action.
+    None,
+    /// Treat the mail as suspicious.
+    Quarantine,
+    /// Reject the mail.
+    Reject,
+}
+
+impl DmarcPolicy {
+    fn parse(value: &str) -> Option<DmarcPolicy> {
+        match value {
+            "none" => Some(DmarcPolicy::None),
+            "quarantine" => Some(DmarcPolicy::Quarantine),
+            "reject" => Some(DmarcPolicy::Reject),
+            _ => None,
+        }
+    }
+}
+
+/// An identifier alignment mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Alignment {
+    /// The domains must share an organizational domain.
+    #[default]
+    Relaxed,
+    /// The domains must be identical.
+    Strict,
+}
+
+impl Alignment {
+    fn parse(value: &str) -> Option<Alignment> {
+        match value {
+            "r" => Some(Alignment::Relaxed),
+            "s" => Some(Alignment::Strict),
+            _ => None,
+        }
+    }
+}
+
+// Returns true if a TXT record's text starts with the `v=DMARC1` tag.
+fn is_dmarc(text: &str) -> bool {
+    let version = text.split(';').next().and_then(|spec| spec.split_once('='));
+    matches!(version, Some((name, value)) if name.trim() == "v" && value.trim() == "DMARC1")
+}
+
+// Splits a comma separated list of URIs.
+fn uri_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// A DMARC policy record.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Dmarc {
+    policy: DmarcPolicy,
+    subdomain_policy: Option<DmarcPolicy>,
+    dkim_alignment: Alignment,
+    spf_alignment: Alignment,
+    percent: u8,
+    aggregate_uris: Vec<String>,
+    failure_uris: Vec<String>,
+    failure_options: Vec<String>,
+    report_interval: u32,
+}
+
+impl Dmarc {
+    /// Parse the text of a DMARC record. Returns `None` if the record doesn't
+    /// start with `v=DMARC1` or has no valid policy. As RFC 7489 requires, a
+    /// record with an invalid domain or subdomain policy but aggregate report
+    /// URIs has policy `none` for both, and other invalid tags take their
+    /// default values.
+    pub fn parse(text: &str) -> Option<Dmarc> {
+        let tags = wire::parse_tags(text)?;
+        match tags.first() {
+            Some(&("v", "DMARC1")) => {}
+            _ => return None,
+        }
+        let mut policy = None;
+        let mut invalid_subdomain_policy = false;
+        let mut dmarc = Dmarc {
+            policy: DmarcPolicy::None,
+            subdomain_policy: None,
+            dkim_alignment: Alignment::Relaxed,
+            spf_alignment: Alignment::Relaxed,
+            percent: 100,
+            aggregate_uris: Vec::new(),
+            failure_uris: Vec::new(),
+            failure_options: vec!["0".to_owned()],
+            report_interval: 86400,
+        };
+        for &(name, value) in &tags[1..] {
+            match name {
+                "p" => policy = DmarcPolicy::parse(value),
+                "sp" => {
+                    dmarc.subdomain_policy = DmarcPolicy::parse(value);
+                    invalid_subdomain_policy = dmarc.subdomain_policy.is_none();
+                }
+                "adkim" => dmarc.dkim_alignment = Alignment::parse(value).unwrap_or_default(),
+                "aspf" => dmarc.spf_alignment = Alignment::parse(value).unwrap_or_default(),
+                "pct" => dmarc.percent = value.parse().ok().filter(|&n| n <= 100).unwrap_or(100),
+                "rua" => dmarc.aggregate_uris = uri_list(value),
+                "ruf" => dmarc.failure_uris = uri_list(value),
+                "fo" => {
+                    dmarc.failure_options = value
+                        .split(':')
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .map(str::to_owned)
+                        .collect()
+                }
+                "ri" => dmarc.report_interval = value.parse().unwrap_or(86400),
+                _ => {}
+            }
+        }
+        match policy {
+            Some(policy) if !invalid_subdomain_policy => dmarc.policy = policy,
+            _ if !dmarc.aggregate_uris.is_empty() => {
+                dmarc.policy = DmarcPolicy::None;
+                dmarc.subdomain_policy = None;
+            }
+            _ => return None,
+        }
+        Some(dmarc)
+    }
+    /// Returns the policy for the domain (`p=`).
+    pub fn policy(&self) -> DmarcPolicy {
+        self.policy
+    }
+    /// Returns the policy for subdomains (`sp=`), which defaults to the domain's
+    /// policy.
+    pub fn subdomain_policy(&self) -> DmarcPolicy {
+        self.subdomain_policy.unwrap_or(self.policy)
+    }
+    /// Returns the DKIM identifier alignment mode (`adkim=`).
+    pub fn dkim_alignment(&self) -> Alignment {
+        self.dkim_alignment
+    }
+    /// Returns the SPF identifier alignment mode (`aspf=`).
+    pub fn spf_alignment(&self) -> Alignment {
+        self.spf_alignment
+    }
+    /// Returns the percentage of failing mail the policy applies to (`pct=`).
+    pub fn percent(&self) -> u8 {
+        self.percent
+    }
+    /// Returns the URIs aggregate reports are sent to (`rua=`).
+    pub fn aggregate_uris(&self) -> &[String] {
+        &self.aggregate_uris
+    }
+    /// Returns the URIs failure reports are sent to (`ruf=`).
+    pub fn failure_uris(&self) -> &[String] {
+        &self.failure_uris
+    }
+    /// Returns the failure reporting options (`fo=`).
+    pub fn failure_options(&self) -> &[String] {
+        &self.failure_options
+    }
+    /// Returns the requested interval between aggregate reports in seconds
+    /// (`ri=`).
+    pub fn report_interval(&self) -> u32 {
+        self.report_interval
+    }
+}
+
+/// A DMARC policy lookup, as returned by
+/// [Context::lookup_dmarc](struct.Context.html#method.lookup_dmarc).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupDmarc {
+    record: Option<Dmarc>,
+    domain: Option<String>,
+    organizational: bool,
+    security: Security,
+}
+
+impl LookupDmarc {
+    /// Returns the record found, if any.
+    pub fn record(&self) -> Option<&Dmarc> {
+        self.record.as_ref()
+    }
+    /// Returns the domain the record was published for, in presentation format
+    /// with a trailing dot.
+    pub fn domain(&self) -> Option<&str> {
+        self.domain.as_deref()
+    }
+    /// Returns true if the record was found at a parent of the domain looked up.
+    pub fn is_organizational(&self) -> bool {
+        self.organizational
+    }
+    /// Returns the policy that applies to the domain looked up: the subdomain
+    /// policy if the record was found at a parent, otherwise the domain's policy.
+    pub fn policy(&self) -> Option<DmarcPolicy> {
+        self.record.as_ref().map(|record| {
+            if self.organizational {
+                record.subdomain_policy()
+            } else {
+                record.policy()
+            }
+        })
+    }
+    /// Returns the outcome of DNSSEC validation of the queries made, combined
+    /// as by [Security::downgrade](enum.Security.html#method.downgrade).
+    pub fn security(&self) -> &Security {
+        &self.security
+    }
+}
+
+impl Context {
+    /// Look up the DMARC policy of a domain at `_dmarc.domain`, falling back to
+    /// its parent domains.
+    ///
+    /// Without a public suffix list the organizational domain isn't known, so the
+    /// parents are walked as in the DMARCbis draft: after the domain itself, at
+    /// most the seven rightmost labels are queried, removing one label at a time
+    /// and stopping before the top-level domain. As RFC 7489 requires, the walk
+    /// ends without a record at a name with several DMARC records or one that
+    /// can't be parsed.
+    pub fn lookup_dmarc(&self, domain: &str) -> Result<LookupDmarc> {
+        let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+        let labels: Vec<_> = domain.split('.').collect();
+        let mut security = Security::Secure;
+        let mut start = 0;
+        loop {
+            let name = labels[start..].join(".");
+            let answer = self
+                .resolve(&format!("_dmarc.{}", name), 16, 1)?
+                .checked()?;
+            security.downgrade(answer.security());
+            let texts: Vec<_> = wire::answer_txt(&answer)
+                .into_iter()
+                .filter(|text| is_dmarc(text))
+                .collect();
+            match texts[..] {
+                [] => {}
+                [ref text] => match Dmarc::parse(text) {
+                    Some(record) => {
+                        return Ok(LookupDmarc {
+                            record: Some(record),
+                            domain: Some(name + "."),
+                            organizational: start > 0,
+                            security,
+                        })
+                    }
+                    None => break,
+                },
+                _ => break,
+            }
+            start = if start == 0 && labels.len() > MAX_WALK_LABELS + 1 {
+                labels.len() - MAX_WALK_LABELS
+            } else {
+                start + 1
+            };
+            if labels.len() - start < 2 {
+                break;
+            }
+        }
+        Ok(LookupDmarc {
+            record: None,
+            domain: None,
+            organizational: false,
+            security,
+        })
+    }
+}
+
+#[test]
+fn test_lookup_dmarc() {
+    let ctx = Context::new().unwrap();
+    ctx.zone_add("dmarc.test.", "static").unwrap();
+    ctx.data_add("_dmarc.dmarc.test. TXT \"v=DMARC1; p=reject; sp=quarantine; \" \"adkim=s; pct=50; rua=mailto:a@dmarc.test, mailto:b@dmarc.test\"")
+        .unwrap();
+    ctx.data_add("_dmarc.own.dmarc.test. TXT \"v=DMARC1; p=bogus; rua=mailto:a@dmarc.test\"")
+        .unwrap();
+    ctx.data_add("_dmarc.two.dmarc.test. TXT \"v=DMARC1; p=none\"")
+        .unwrap();
+    ctx.data_add("_dmarc.two.dmarc.test. TXT \"v=DMARC1; p=none; pct=1\"")
+        .unwrap();
+    ctx.data_add("_dmarc.bad.dmarc.test. TXT \"v=DMARC1; p=bogus\"")
+        .unwrap();
+    let lookup = ctx.lookup_dmarc("dmarc.test").unwrap();
+    assert_eq!(lookup.domain(), Some("dmarc.test."));
+    assert!(!lookup.is_organizational());
+    assert_eq!(lookup.policy(), Some(DmarcPolicy::Reject));
+    let record = lookup.record().unwrap();
+    assert_eq!(record.dkim_alignment(), Alignment::Strict);
+    assert_eq!(record.spf_alignment(), Alignment::Relaxed);
+    assert_eq!(record.percent(), 50);
+    assert_eq!(
+        record.aggregate_uris(),
+        ["mailto:a@dmarc.test", "mailto:b@dmarc.test"]
+    );
+    let lookup = ctx.lookup_dmarc("a.b.dmarc.test.").unwrap();
+    assert_eq!(lookup.domain(), Some("dmarc.test."));
+    assert!(lookup.is_organizational());
+    assert_eq!(lookup.policy(), Some(DmarcPolicy::Quarantine));
+    let lookup = ctx.lookup_dmarc("own.dmarc.test").unwrap();
+    assert_eq!(lookup.policy(), Some(DmarcPolicy::None));
+    // Several records, or an invalid one, end the walk.
+    let lookup = ctx.lookup_dmarc("two.dmarc.test").unwrap();
+    assert_eq!(lookup.record(), None);
+    assert_eq!(lookup.domain(), None);
+    let lookup = ctx.lookup_dmarc("a.bad.dmarc.test").unwrap();
+    assert_eq!(lookup.record(), None);
+    assert_eq!(Dmarc::parse("p=reject; v=DMARC1"), None);
+    assert_eq!(Dmarc::parse("v=DMARC1; p=bogus"), None);
+    assert_eq!(Dmarc::parse("v=DMARC1; p=reject; sp=bogus"), None);
+    let dmarc = Dmarc::parse("v=DMARC1; p=reject; sp=bogus; rua=mailto:d@dmarc.test").unwrap();
+    assert_eq!(dmarc.policy(), DmarcPolicy::None);
+    assert_eq!(dmarc.subdomain_policy(), DmarcPolicy::None);
+}
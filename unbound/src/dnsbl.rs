@@ -0,0 +1,176 @@
+//! DNS blocklist queries ([RFC 5782](https://tools.ietf.org/html/rfc5782)).
+use std::net::{IpAddr, Ipv4Addr};
+
+use super::ip::data_to_ipv4;
+use super::reverse_name;
+use super::wire;
+use super::{Context, Error, Result, Security};
+
+/// Whether a blocklist lists an address or domain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DnsblStatus {
+    /// The blocklist has no entry.
+    NotListed,
+    /// The blocklist has an entry.
+    Listed,
+    /// The blocklist answered only with codes in `127.255.255.0/24`, which lists
+    /// use to refuse queries, as from public resolvers.
+    Refused,
+    /// The query failed.
+    Error(Error),
+}
+
+/// The answer of one blocklist, as returned by
+/// [Context::check_dnsbl](struct.Context.html#method.check_dnsbl) and
+/// [Context::check_rhsbl](struct.Context.html#method.check_rhsbl).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DnsblResult {
+    zone: String,
+    status: DnsblStatus,
+    codes: Vec<Ipv4Addr>,
+    reasons: Vec<String>,
+    security: Option<Security>,
+}
+
+impl DnsblResult {
+    /// Returns the blocklist zone.
+    pub fn zone(&self) -> &str {
+        &self.zone
+    }
+    /// Returns whether the blocklist lists the address or domain.
+    pub fn status(&self) -> &DnsblStatus {
+        &self.status
+    }
+    /// Returns true if the blocklist lists the address or domain.
+    pub fn is_listed(&self) -> bool {
+        self.status == DnsblStatus::Listed
+    }
+    /// Returns the `127.0.0.0/8` addresses returned, whose meaning is defined by
+    /// each list.
+    pub fn codes(&self) -> &[Ipv4Addr] {
+        &self.codes
+    }
+    /// Returns the reasons published in TXT records for a listing.
+    pub fn reasons(&self) -> &[String] {
+        &self.reasons
+    }
+    /// Returns the outcome of DNSSEC validation of the A query, unless it failed.
+    pub fn security(&self) -> Option<&Security> {
+        self.security.as_ref()
+    }
+}
+
+impl Context {
+    /// Check an address against DNS blocklists. The query names are the
+    /// address's reversed octets, or nibbles for IPv6, prepended to each zone.
+    /// The A queries for every zone are made concurrently, followed by TXT
+    /// queries for the reasons of the zones that list the address. Results are
+    /// returned in the order of `zones`.
+    pub fn check_dnsbl(&self, ip: IpAddr, zones: &[&str]) -> Result<Vec<DnsblResult>> {
+        let name = reverse_name(&ip);
+        let suffix = if ip.is_ipv4() {
+            "in-addr.arpa."
+        } else {
+            "ip6.arpa."
+        };
+        self.check_blocklists(&name[..name.len() - suffix.len()], zones)
+    }
+    /// Check a domain against domain-based blocklists (RHSBLs), querying the
+    /// domain prepended to each zone.
+    pub fn check_rhsbl(&self, domain: &str, zones: &[&str]) -> Result<Vec<DnsblResult>> {
+        self.check_blocklists(&format!("{}.", domain.trim_end_matches('.')), zones)
+    }
+    // Queries `prefix` followed by each zone, where `prefix` ends with a dot.
+    fn check_blocklists(&self, prefix: &str, zones: &[&str]) -> Result<Vec<DnsblResult>> {
+        let names: Vec<_> = zones
+            .iter()
+            .map(|zone| format!("{}{}", prefix, zone))
+            .collect();
+        let queries: Vec<_> = names.iter().map(|name| (&name[..], 1, 1)).collect();
+        let mut results = self.resolve_all(&queries)?.into_iter();
+        let mut checks = Vec::with_capacity(zones.len());
+        for zone in zones {
+            let a = results.next().unwrap_or(Err(Error::Cancelled));
+            let mut check = DnsblResult {
+                zone: zone.to_string(),
+                status: DnsblStatus::NotListed,
+                codes: Vec::new(),
+                reasons: Vec::new(),
+                security: None,
+            };
+            match a.and_then(|answer| answer.checked()) {
+                Ok(answer) => {
+                    check.codes = answer
+                        .data()
+                        .filter_map(data_to_ipv4)
+                        .filter(|addr| addr.octets()[0] == 127)
+                        .collect();
+                    let refused = |addr: &Ipv4Addr| addr.octets()[..3] == [127, 255, 255];
+                    if check.codes.iter().any(|addr| !refused(addr)) {
+                        check.status = DnsblStatus::Listed;
+                    } else if !check.codes.is_empty() {
+                        check.status = DnsblStatus::Refused;
+                    }
+                    check.security = Some(answer.security());
+                }
+                Err(err) => check.status = DnsblStatus::Error(err),
+            }
+            checks.push(check);
+        }
+        let listed: Vec<_> = (0..checks.len())
+            .filter(|&i| checks[i].is_listed())
+            .collect();
+        let queries: Vec<_> = listed.iter().map(|&i| (&names[i][..], 16, 1)).collect();
+        for (&i, txt) in listed.iter().zip(self.resolve_all(&queries)?) {
+            if let Ok(answer) = txt {
+                checks[i].reasons = wire::answer_txt(&answer);
+            }
+        }
+        Ok(checks)
+    }
+}
+
+#[test]
+fn test_check_dnsbl() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    for zone in &["bl.test.", "clean.test.", "refuse.test."] {
+        ctx.zone_add(zone, "static").unwrap();
+    }
+    ctx.data_add("2.0.0.127.bl.test. A 127.0.0.2").unwrap();
+    ctx.data_add("2.0.0.127.bl.test. A 127.0.0.4").unwrap();
+    ctx.data_add("2.0.0.127.bl.test. TXT \"listed for \" \"testing\"")
+        .unwrap();
+    ctx.data_add("2.0.0.127.refuse.test. A 127.255.255.254")
+        .unwrap();
+    ctx.data_add("2.0.0.127.refuse.test. TXT \"public resolver\"")
+        .unwrap();
+    ctx.data_add(
+        "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.bl.test. A 127.0.0.3",
+    )
+    .unwrap();
+    ctx.data_add("spam.example.bl.test. A 127.0.0.5").unwrap();
+    let zones = ["bl.test", "clean.test", "refuse.test"];
+    let checks = ctx
+        .check_dnsbl("127.0.0.2".parse().unwrap(), &zones)
+        .unwrap();
+    assert_eq!(checks[0].zone(), "bl.test");
+    assert!(checks[0].is_listed());
+    let mut codes = checks[0].codes().to_vec();
+    codes.sort();
+    assert_eq!(
+        codes,
+        [Ipv4Addr::new(127, 0, 0, 2), Ipv4Addr::new(127, 0, 0, 4)]
+    );
+    assert_eq!(checks[0].reasons(), ["listed for testing"]);
+    assert_eq!(*checks[1].status(), DnsblStatus::NotListed);
+    assert_eq!(*checks[2].status(), DnsblStatus::Refused);
+    assert!(checks[2].reasons().is_empty());
+    let checks = ctx
+        .check_dnsbl("2001:db8::1".parse().unwrap(), &zones)
+        .unwrap();
+    assert_eq!(checks[0].codes(), [Ipv4Addr::new(127, 0, 0, 3)]);
+    assert!(checks[0].reasons().is_empty());
+    let checks = ctx.check_rhsbl("spam.example.", &zones[..1]).unwrap();
+    assert_eq!(checks[0].codes(), [Ipv4Addr::new(127, 0, 0, 5)]);
+}
@@ -0,0 +1,281 @@
+//! DNS-based service discovery ([RFC 6763](https://tools.ietf.org/html/rfc6763)).
+use std::convert::TryFrom;
+
+use super::srv;
+use super::{Answer, Context, Error, LookupPtr, Result, Security};
+
+/// A resolved service instance, as returned by
+/// [Context::resolve_service](struct.Context.html#method.resolve_service).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceInstance {
+    name: String,
+    instance: String,
+    priority: u16,
+    weight: u16,
+    port: u16,
+    host: String,
+    attributes: Vec<(String, Option<Vec<u8>>)>,
+    security: Security,
+}
+
+impl ServiceInstance {
+    /// Returns the full name of the instance in presentation format with a
+    /// trailing dot.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Returns the user-visible instance name, the first label of the full name
+    /// without escapes.
+    pub fn instance(&self) -> &str {
+        &self.instance
+    }
+    /// Returns the priority of the SRV record used.
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+    /// Returns the weight of the SRV record used.
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+    /// Returns the port of the service.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+    /// Returns the name of the host providing the service in presentation format
+    /// with a trailing dot.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+    /// Returns the TXT record attributes in order, with keys in lowercase.
+    /// Attributes without an `=` have no value.
+    pub fn attributes(&self) -> &[(String, Option<Vec<u8>>)] {
+        &self.attributes
+    }
+    /// Returns an attribute by key, ignoring case: `None` if absent,
+    /// `Some(None)` if present without a value, otherwise its value, which may be
+    /// empty.
+    pub fn attribute(&self, key: &str) -> Option<Option<&[u8]>> {
+        self.attributes
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_deref())
+    }
+    /// Returns the outcome of DNSSEC validation of the SRV and TXT queries,
+    /// combined as by [Security::downgrade](enum.Security.html#method.downgrade).
+    pub fn security(&self) -> &Security {
+        &self.security
+    }
+}
+
+// Returns the first label of a name in presentation format, without escapes.
+fn first_label(name: &str) -> String {
+    let name = name.as_bytes();
+    let mut label = Vec::new();
+    let mut i = 0;
+    while i < name.len() && name[i] != b'.' {
+        if name[i] != b'\\' {
+            label.push(name[i]);
+            i += 1;
+            continue;
+        }
+        match name.get(i + 1..i + 4) {
+            Some(digits) if digits.iter().all(u8::is_ascii_digit) => {
+                let n = digits
+                    .iter()
+                    .fold(0u32, |n, &d| n * 10 + u32::from(d - b'0'));
+                // An escape beyond a byte's range is invalid, so keep it as is.
+                match u8::try_from(n) {
+                    Ok(byte) => label.push(byte),
+                    Err(_) => label.extend_from_slice(&name[i..i + 4]),
+                }
+                i += 4;
+            }
+            _ => {
+                label.extend(name.get(i + 1));
+                i += 2;
+            }
+        }
+    }
+    String::from_utf8_lossy(&label).into_owned()
+}
+
+// Parses the character-strings of a DNS-SD TXT record into attributes. Strings
+// that are empty or start with `=` are ignored, as are repeated keys.
+fn parse_attributes(data: &[u8]) -> Vec<(String, Option<Vec<u8>>)> {
+    let mut attributes: Vec<(String, Option<Vec<u8>>)> = Vec::new();
+    let mut rest = data;
+    while let Some((&len, tail)) = rest.split_first() {
+        let string = match tail.get(..len as usize) {
+            Some(string) => string,
+            None => break,
+        };
+        rest = &tail[len as usize..];
+        let (key, value) = match string.iter().position(|&b| b == b'=') {
+            Some(i) => (&string[..i], Some(string[i + 1..].to_vec())),
+            None => (string, None),
+        };
+        if key.is_empty() || !key.iter().all(|&b| (0x20..=0x7E).contains(&b)) {
+            continue;
+        }
+        let key = String::from_utf8_lossy(key).to_ascii_lowercase();
+        if !attributes.iter().any(|(k, _)| *k == key) {
+            attributes.push((key, value));
+        }
+    }
+    attributes
+}
+
+// Builds an instance from the answers to its SRV and TXT queries, returning
+// `None` if it has no usable SRV record.
+fn from_answers(name: &str, srv: Answer, txt: Answer) -> Option<ServiceInstance> {
+    let records: Vec<_> = srv.data().filter_map(srv::parse).collect();
+    let (priority, weight, port, host) = srv::order(records).into_iter().next()?;
+    if host == "." {
+        return None;
+    }
+    let mut security = srv.security();
+    security.downgrade(txt.security());
+    Some(ServiceInstance {
+        name: name.to_owned(),
+        instance: first_label(name),
+        priority,
+        weight,
+        port,
+        host,
+        attributes: txt.data().next().map(parse_attributes).unwrap_or_default(),
+        security,
+    })
+}
+
+// Joins a service type and domain, as in `_http._tcp` and `example.com`.
+fn service_name(service_type: &str, domain: &str) -> String {
+    format!(
+        "{}.{}",
+        service_type.trim_end_matches('.'),
+        domain.trim_end_matches('.')
+    )
+}
+
+impl Context {
+    /// Enumerate the service types advertised in a domain, as names such as
+    /// `_http._tcp.example.com.`.
+    pub fn lookup_service_types(&self, domain: &str) -> Result<LookupPtr> {
+        let name = service_name("_services._dns-sd._udp", domain);
+        let answer = self.resolve(&name, 12, 1)?.checked()?;
+        Ok(LookupPtr::from_answer(&answer))
+    }
+    /// Browse the instances of a service type, such as `_ipp._tcp`, in a
+    /// domain, returning their full names.
+    pub fn browse_services(&self, service_type: &str, domain: &str) -> Result<LookupPtr> {
+        let name = service_name(service_type, domain);
+        let answer = self.resolve(&name, 12, 1)?.checked()?;
+        Ok(LookupPtr::from_answer(&answer))
+    }
+    /// Resolve a service instance by its full name, looking up its SRV and TXT
+    /// records concurrently. Returns `None` if the instance has no SRV record,
+    /// or one with a target of ".".
+    pub fn resolve_service(&self, name: &str) -> Result<Option<ServiceInstance>> {
+        let mut results = self
+            .resolve_all(&[(name, 33, 1), (name, 16, 1)])?
+            .into_iter();
+        let srv = results.next().unwrap_or(Err(Error::Cancelled))?.checked()?;
+        let txt = results.next().unwrap_or(Err(Error::Cancelled))?.checked()?;
+        Ok(from_answers(name, srv, txt))
+    }
+    /// Browse the instances of a service type and resolve them all
+    /// concurrently. Instances whose queries fail, or that can't be resolved to
+    /// an SRV record, are skipped; use
+    /// [resolve_service](struct.Context.html#method.resolve_service) to find out
+    /// why.
+    pub fn discover_services(
+        &self,
+        service_type: &str,
+        domain: &str,
+    ) -> Result<Vec<ServiceInstance>> {
+        let names = self.browse_services(service_type, domain)?.names().to_vec();
+        let queries: Vec<_> = names
+            .iter()
+            .flat_map(|name| vec![(&name[..], 33, 1), (&name[..], 16, 1)])
+            .collect();
+        let mut results = self.resolve_all(&queries)?.into_iter();
+        let mut instances = Vec::with_capacity(names.len());
+        for name in &names {
+            let srv = results.next().unwrap_or(Err(Error::Cancelled));
+            let txt = results.next().unwrap_or(Err(Error::Cancelled));
+            if let (Ok(srv), Ok(txt)) =
+                (srv.and_then(Answer::checked), txt.and_then(Answer::checked))
+            {
+                instances.extend(from_answers(name, srv, txt));
+            }
+        }
+        Ok(instances)
+    }
+}
+
+#[test]
+fn test_discover_services() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    ctx.zone_add("sd.test.", "static").unwrap();
+    for data in &[
+        "_services._dns-sd._udp.sd.test. PTR _ipp._tcp.sd.test.",
+        "_services._dns-sd._udp.sd.test. PTR _http._tcp.sd.test.",
+        "_ipp._tcp.sd.test. PTR Office\\032Printer._ipp._tcp.sd.test.",
+        "_ipp._tcp.sd.test. PTR Lab\\.2._ipp._tcp.sd.test.",
+        "_ipp._tcp.sd.test. PTR Gone._ipp._tcp.sd.test.",
+        "_ipp._tcp.sd.test. PTR Far._ipp._tcp.far.test.",
+        "Office\\032Printer._ipp._tcp.sd.test. SRV 0 0 631 printer.sd.test.",
+        "Office\\032Printer._ipp._tcp.sd.test. TXT \"txtvers=1\" \"PDL=application/pdf\" \"Color\" \"note=\" \"pdl=x\" \"=x\"",
+        "Lab\\.2._ipp._tcp.sd.test. SRV 0 0 8631 lab.sd.test.",
+        "Gone._ipp._tcp.sd.test. TXT \"\"",
+    ] {
+        ctx.data_add(data).unwrap();
+    }
+    let mut types = ctx
+        .lookup_service_types("sd.test")
+        .unwrap()
+        .names()
+        .to_vec();
+    types.sort();
+    assert_eq!(types, ["_http._tcp.sd.test.", "_ipp._tcp.sd.test."]);
+    assert_eq!(
+        ctx.browse_services("_ipp._tcp", "sd.test.")
+            .unwrap()
+            .names()
+            .len(),
+        4
+    );
+    // The instance outside the local zone fails to resolve offline, or times
+    // out, and is skipped.
+    ctx.set_lookup_timeout(std::time::Duration::from_millis(500));
+    let mut instances = ctx.discover_services("_ipp._tcp", "sd.test").unwrap();
+    instances.sort_by_key(|i| i.port());
+    assert_eq!(instances.len(), 2);
+    let printer = &instances[0];
+    assert_eq!(printer.instance(), "Office Printer");
+    assert_eq!(printer.host(), "printer.sd.test.");
+    assert_eq!(printer.port(), 631);
+    assert_eq!(
+        printer.attribute("pdl"),
+        Some(Some(&b"application/pdf"[..]))
+    );
+    assert_eq!(printer.attribute("color"), Some(None));
+    assert_eq!(printer.attribute("note"), Some(Some(&b""[..])));
+    assert_eq!(printer.attribute("missing"), None);
+    assert_eq!(printer.attributes().len(), 4);
+    assert_eq!(instances[1].instance(), "Lab.2");
+    assert!(instances[1].attributes().is_empty());
+    let lab = ctx.resolve_service(instances[1].name()).unwrap().unwrap();
+    assert_eq!(lab.port(), 8631);
+    assert_eq!(ctx.resolve_service("Gone._ipp._tcp.sd.test").unwrap(), None);
+}
+
+#[test]
+fn test_first_label() {
+    assert_eq!(
+        first_label(r"Office\032Printer._ipp._tcp.sd.test."),
+        "Office Printer"
+    );
+    assert_eq!(first_label(r"Lab\.2._ipp._tcp.sd.test."), "Lab.2");
+    assert_eq!(first_label(r"Big\256._ipp._tcp.sd.test."), r"Big\256");
+}
@@ -0,0 +1,213 @@
+//! Extended DNS Errors ([RFC 8914](https://tools.ietf.org/html/rfc8914)).
+use std::fmt;
+
+use super::wire::{self, Message};
+
+// EDNS option code of Extended DNS Error.
+const OPTION_CODE: u16 = 15;
+
+macro_rules! info_codes {
+    ($($(#[$doc:meta])* $name:ident = $code:expr, $text:expr;)*) => {
+        /// An Extended DNS Error INFO-CODE.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum InfoCode {
+            $($(#[$doc])* $name,)*
+            /// A code not known to this crate.
+            Unknown(u16),
+        }
+
+        impl InfoCode {
+            /// Returns the numeric value of the code.
+            pub fn code(&self) -> u16 {
+                match *self {
+                    $(InfoCode::$name => $code,)*
+                    InfoCode::Unknown(code) => code,
+                }
+            }
+            /// Returns the code's purpose as listed in the IANA registry.
+            pub fn purpose(&self) -> Option<&'static str> {
+                match *self {
+                    $(InfoCode::$name => Some($text),)*
+                    InfoCode::Unknown(_) => None,
+                }
+            }
+        }
+
+        impl From<u16> for InfoCode {
+            fn from(code: u16) -> InfoCode {
+                match code {
+                    $($code => InfoCode::$name,)*
+                    code => InfoCode::Unknown(code),
+                }
+            }
+        }
+    };
+}
+
+info_codes! {
+    /// The error doesn't match a more specific code.
+    Other = 0, "Other Error";
+    /// A DNSKEY RRset used an unsupported algorithm.
+    UnsupportedDnskeyAlgorithm = 1, "Unsupported DNSKEY Algorithm";
+    /// A DS RRset used an unsupported digest type.
+    UnsupportedDsDigestType = 2, "Unsupported DS Digest Type";
+    /// The answer was served from cache after its TTL expired.
+    StaleAnswer = 3, "Stale Answer";
+    /// The answer was forged by policy.
+    ForgedAnswer = 4, "Forged Answer";
+    /// DNSSEC validation ended in the Indeterminate state.
+    DnssecIndeterminate = 5, "DNSSEC Indeterminate";
+    /// DNSSEC validation ended in the Bogus state.
+    DnssecBogus = 6, "DNSSEC Bogus";
+    /// Only expired signatures were found.
+    SignatureExpired = 7, "Signature Expired";
+    /// Only signatures not yet valid were found.
+    SignatureNotYetValid = 8, "Signature Not Yet Valid";
+    /// No DNSKEY matched the DS of a secure delegation.
+    DnskeyMissing = 9, "DNSKEY Missing";
+    /// Signatures were expected but not found.
+    RrsigsMissing = 10, "RRSIGs Missing";
+    /// No DNSKEY had the Zone Key bit set.
+    NoZoneKeyBitSet = 11, "No Zone Key Bit Set";
+    /// The zone's denial of existence couldn't be validated.
+    NsecMissing = 12, "NSEC Missing";
+    /// A cached SERVFAIL was returned.
+    CachedError = 13, "Cached Error";
+    /// The server is not ready to serve the zone.
+    NotReady = 14, "Not Ready";
+    /// The query was blocked by the operator's policy.
+    Blocked = 15, "Blocked";
+    /// The query was blocked by an external requirement.
+    Censored = 16, "Censored";
+    /// The query was blocked at the client's request.
+    Filtered = 17, "Filtered";
+    /// The client isn't permitted to query the server.
+    Prohibited = 18, "Prohibited";
+    /// A stale NXDOMAIN was returned.
+    StaleNxdomainAnswer = 19, "Stale NXDOMAIN Answer";
+    /// The server isn't authoritative for the zone.
+    NotAuthoritative = 20, "Not Authoritative";
+    /// The operation isn't supported.
+    NotSupported = 21, "Not Supported";
+    /// No authoritative server could be reached.
+    NoReachableAuthority = 22, "No Reachable Authority";
+    /// A network error prevented resolution.
+    NetworkError = 23, "Network Error";
+    /// The zone's data is invalid.
+    InvalidData = 24, "Invalid Data";
+    /// Signatures expired before they became valid.
+    SignatureExpiredBeforeValid = 25, "Signature Expired before Valid";
+    /// The query arrived too early to be answered.
+    TooEarly = 26, "Too Early";
+    /// An NSEC3 RRset had an unsupported iterations value.
+    UnsupportedNsec3IterationsValue = 27, "Unsupported NSEC3 Iterations Value";
+    /// The server couldn't conform to the query's policy.
+    UnableToConformToPolicy = 28, "Unable to conform to policy";
+    /// The answer was synthesized.
+    Synthesized = 29, "Synthesized";
+    /// The query type isn't supported.
+    InvalidQueryType = 30, "Invalid Query Type";
+}
+
+impl fmt::Display for InfoCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.purpose() {
+            Some(purpose) => write!(f, "{} ({})", self.code(), purpose),
+            None => write!(f, "{}", self.code()),
+        }
+    }
+}
+
+/// An Extended DNS Error carried in an answer packet's OPT record.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ExtendedError {
+    info_code: InfoCode,
+    extra_text: Option<String>,
+}
+
+impl ExtendedError {
+    /// Returns the INFO-CODE.
+    pub fn info_code(&self) -> InfoCode {
+        self.info_code
+    }
+    /// Returns the EXTRA-TEXT, if any.
+    pub fn extra_text(&self) -> Option<&str> {
+        self.extra_text.as_deref()
+    }
+}
+
+impl fmt::Display for ExtendedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.info_code.fmt(f)?;
+        match self.extra_text {
+            Some(ref text) => write!(f, ": ({})", text),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Returns the Extended DNS Errors in the options of an OPT record's data.
+/// Malformed options end parsing.
+pub(crate) fn from_options(mut options: &[u8]) -> Vec<ExtendedError> {
+    let mut errors = Vec::new();
+    while let (Some(code), Some(len)) = (wire::read_u16(options, 0), wire::read_u16(options, 2)) {
+        let data = match options.get(4..4 + len as usize) {
+            Some(data) => data,
+            None => break,
+        };
+        if code == OPTION_CODE {
+            if let Some(info_code) = wire::read_u16(data, 0) {
+                let text = String::from_utf8_lossy(&data[2..]);
+                let text = text.trim_end_matches('\0');
+                errors.push(ExtendedError {
+                    info_code: InfoCode::from(info_code),
+                    extra_text: if text.is_empty() {
+                        None
+                    } else {
+                        Some(text.to_owned())
+                    },
+                });
+            }
+        }
+        options = &options[4 + len as usize..];
+    }
+    errors
+}
+
+/// Returns the Extended DNS Errors in an answer packet.
+pub(crate) fn from_packet(packet: Option<&[u8]>) -> Vec<ExtendedError> {
+    packet
+        .and_then(Message::parse)
+        .map(|msg| {
+            msg.additional
+                .iter()
+                .filter(|r| r.rrtype == 41)
+                .flat_map(|r| from_options(r.rdata()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_from_packet() {
+    let mut packet = vec![0, 0, 0x81, 0x82, 0, 0, 0, 0, 0, 0, 0, 1];
+    // Root owner, type OPT, class (UDP size) 1232, TTL 0
+    packet.extend_from_slice(&[0, 0, 41, 0x04, 0xD0, 0, 0, 0, 0]);
+    let options = [
+        &[0, 10, 0, 2, 0xAA, 0xBB][..],
+        &[0, 15, 0, 2, 0, 6][..],
+        &[0, 15, 0, 9, 0, 15, b'b', b'l', b'o', b'c', b'k', b'e', b'd'][..],
+    ]
+    .concat();
+    packet.extend_from_slice(&[0, options.len() as u8]);
+    packet.extend_from_slice(&options);
+    let errors = from_packet(Some(&packet));
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].info_code(), InfoCode::DnssecBogus);
+    assert_eq!(errors[0].extra_text(), None);
+    assert_eq!(errors[1].info_code(), InfoCode::Blocked);
+    assert_eq!(errors[1].extra_text(), Some("blocked"));
+    assert_eq!(errors[1].to_string(), "15 (Blocked): (blocked)");
+    assert_eq!(InfoCode::from(4242), InfoCode::Unknown(4242));
+    assert!(from_packet(None).is_empty());
+}
@@ -0,0 +1,223 @@
+//! Address lookups.
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use super::{Answer, Context, Error, Result, Security};
+
+/// Which address families `lookup_ip` queries for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum IpStrategy {
+    /// Query for A records only.
+    Ipv4Only,
+    /// Query for AAAA records only.
+    Ipv6Only,
+    /// Query for both A and AAAA records.
+    #[default]
+    Both,
+}
+
+/// The addresses of a host, as returned by
+/// [Context::lookup_ip](struct.Context.html#method.lookup_ip).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupIp {
+    canonname: Option<String>,
+    addrs: Vec<(IpAddr, u32)>,
+    ipv4: Option<Result<Security>>,
+    ipv6: Option<Result<Security>>,
+}
+
+impl LookupIp {
+    /// Returns an iterator over the addresses found, IPv6 before IPv4.
+    pub fn iter(&self) -> LookupIpIter<'_> {
+        LookupIpIter(self.addrs.iter())
+    }
+    /// Returns the addresses found along with the number of seconds each is valid.
+    pub fn addrs_with_ttl(&self) -> &[(IpAddr, u32)] {
+        &self.addrs
+    }
+    /// Returns true if no addresses were found.
+    pub fn is_empty(&self) -> bool {
+        self.addrs.is_empty()
+    }
+    /// Returns the canonical name of the host, if it differs from the name looked up.
+    pub fn canonname(&self) -> Option<&str> {
+        self.canonname.as_deref()
+    }
+    /// Returns the outcome of DNSSEC validation of the A query, if made and
+    /// answered.
+    pub fn ipv4_security(&self) -> Option<&Security> {
+        self.ipv4.as_ref().and_then(|r| r.as_ref().ok())
+    }
+    /// Returns the outcome of DNSSEC validation of the AAAA query, if made and
+    /// answered.
+    pub fn ipv6_security(&self) -> Option<&Security> {
+        self.ipv6.as_ref().and_then(|r| r.as_ref().ok())
+    }
+    /// Returns the error the A query failed with, if it did.
+    pub fn ipv4_error(&self) -> Option<&Error> {
+        self.ipv4.as_ref().and_then(|r| r.as_ref().err())
+    }
+    /// Returns the error the AAAA query failed with, if it did.
+    pub fn ipv6_error(&self) -> Option<&Error> {
+        self.ipv6.as_ref().and_then(|r| r.as_ref().err())
+    }
+    /// Returns true if every query made was answered and secure.
+    pub fn is_secure(&self) -> bool {
+        self.ipv4
+            .iter()
+            .chain(self.ipv6.iter())
+            .all(|r| r.as_ref().is_ok_and(Security::is_secure))
+    }
+}
+
+/// An iterator over the addresses of a [LookupIp](struct.LookupIp.html).
+#[derive(Clone, Debug)]
+pub struct LookupIpIter<'a>(std::slice::Iter<'a, (IpAddr, u32)>);
+
+impl<'a> Iterator for LookupIpIter<'a> {
+    type Item = IpAddr;
+    fn next(&mut self) -> Option<IpAddr> {
+        self.0.next().map(|&(addr, _)| addr)
+    }
+}
+
+/// Converts A record data to an address.
+pub(crate) fn data_to_ipv4(data: &[u8]) -> Option<Ipv4Addr> {
+    if data.len() == 4 {
+        Some(Ipv4Addr::new(data[0], data[1], data[2], data[3]))
+    } else {
+        None
+    }
+}
+
+/// Converts AAAA record data to an address.
+pub(crate) fn data_to_ipv6(data: &[u8]) -> Option<Ipv6Addr> {
+    if data.len() == 16 {
+        let mut octets = [0; 16];
+        octets.copy_from_slice(data);
+        Some(Ipv6Addr::from(octets))
+    } else {
+        None
+    }
+}
+
+/// Returns the addresses in an A or AAAA answer.
+pub(crate) fn answer_addrs(answer: &Answer) -> Vec<IpAddr> {
+    match answer.qtype() {
+        1 => answer
+            .data()
+            .filter_map(data_to_ipv4)
+            .map(IpAddr::V4)
+            .collect(),
+        28 => answer
+            .data()
+            .filter_map(data_to_ipv6)
+            .map(IpAddr::V6)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Collects the results of A and AAAA queries for a name, each paired with the
+/// type queried. Fails only if every query failed.
+pub(crate) fn from_answers<I>(results: I) -> Result<LookupIp>
+where
+    I: IntoIterator<Item = (u16, Result<Answer>)>,
+{
+    let mut lookup = LookupIp {
+        canonname: None,
+        addrs: Vec::new(),
+        ipv4: None,
+        ipv6: None,
+    };
+    let mut error = None;
+    let mut answered = false;
+    for (rrtype, result) in results {
+        let outcome = match result.and_then(Answer::checked) {
+            Ok(answer) => {
+                let ttl = answer.ttl();
+                lookup
+                    .addrs
+                    .extend(answer_addrs(&answer).into_iter().map(|addr| (addr, ttl)));
+                if lookup.canonname.is_none() {
+                    lookup.canonname = answer.canonname().map(str::to_owned);
+                }
+                answered = true;
+                Ok(answer.security())
+            }
+            Err(err) => {
+                error.get_or_insert_with(|| err.clone());
+                Err(err)
+            }
+        };
+        match rrtype {
+            1 => lookup.ipv4 = Some(outcome),
+            _ => lookup.ipv6 = Some(outcome),
+        }
+    }
+    match error {
+        Some(err) if !answered => Err(err),
+        _ => Ok(lookup),
+    }
+}
+
+impl Context {
+    /// Look up the IPv4 and IPv6 addresses of a host.
+    /// Equivalent to `lookup_ip_with(name, IpStrategy::Both)`.
+    pub fn lookup_ip(&self, name: &str) -> Result<LookupIp> {
+        self.lookup_ip_with(name, IpStrategy::Both)
+    }
+    /// Look up the addresses of a host. When both families are wanted the A and
+    /// AAAA queries are made concurrently with `resolve_async`. Fails only if
+    /// every query fails; otherwise the error a query failed with is available
+    /// from `ipv4_error` or `ipv6_error`.
+    pub fn lookup_ip_with(&self, name: &str, strategy: IpStrategy) -> Result<LookupIp> {
+        let rrtypes: &[u16] = match strategy {
+            IpStrategy::Ipv4Only => &[1],
+            IpStrategy::Ipv6Only => &[28],
+            IpStrategy::Both => &[28, 1],
+        };
+        let queries: Vec<_> = rrtypes.iter().map(|&t| (name, t, 1)).collect();
+        from_answers(rrtypes.iter().cloned().zip(self.resolve_all(&queries)?))
+    }
+}
+
+#[test]
+fn test_lookup_ip() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    let lookup = ctx.lookup_ip("localhost").unwrap();
+    let addrs: Vec<_> = lookup.iter().collect();
+    assert_eq!(
+        addrs,
+        [
+            IpAddr::from(Ipv6Addr::LOCALHOST),
+            IpAddr::from(Ipv4Addr::LOCALHOST)
+        ]
+    );
+    assert_eq!(lookup.ipv4_security(), Some(&Security::Insecure));
+    assert!(!lookup.is_secure());
+    let lookup = ctx
+        .lookup_ip_with("localhost", IpStrategy::Ipv4Only)
+        .unwrap();
+    assert_eq!(lookup.iter().collect::<Vec<_>>(), [Ipv4Addr::LOCALHOST]);
+    assert_eq!(lookup.ipv6_security(), None);
+}
+
+#[test]
+fn test_lookup_ip_partial() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    ctx.set_fwd4(Ipv4Addr::new(192, 0, 2, 1)).unwrap();
+    ctx.set_lookup_timeout(std::time::Duration::from_millis(100));
+    ctx.zone_add("ip.test.", "typetransparent").unwrap();
+    ctx.data_add("a.ip.test. A 192.0.2.10").unwrap();
+    let lookup = ctx.lookup_ip("a.ip.test").unwrap();
+    assert_eq!(
+        lookup.iter().collect::<Vec<_>>(),
+        [Ipv4Addr::new(192, 0, 2, 10)]
+    );
+    assert_eq!(lookup.ipv4_error(), None);
+    assert_eq!(lookup.ipv6_error(), Some(&Error::TimedOut));
+    assert_eq!(lookup.ipv6_security(), None);
+    assert!(!lookup.is_secure());
+}
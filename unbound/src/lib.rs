@@ -14,8 +14,17 @@
 //! *Note:* A panic during a callback will lead to an abort in Rust 1.24 and later.
 //! In earlier releases Rust will try to unwind which will not go well.
 //!
+#[cfg(feature = "crossbeam-channel")]
+extern crate crossbeam_channel;
 extern crate libc;
+extern crate openssl;
+#[cfg(feature = "rustls")]
+extern crate rustls;
+#[cfg(feature = "serde")]
+extern crate serde;
 extern crate unbound_sys as sys;
+#[cfg(feature = "rustls")]
+extern crate webpki;
 
 use std::borrow::Borrow;
 use std::cell::UnsafeCell;
@@ -23,16 +32,75 @@ use std::ffi::{CStr, CString, NulError};
 use std::io::{self, Write};
 use std::path::Path;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use std::{fmt, net, ptr};
 
 use libc::{c_char, c_int, c_void};
 
+mod caa;
+mod channel;
+mod coalesce;
+mod dane;
+mod dkim;
+mod dmarc;
+mod dnsbl;
+mod dnssd;
+mod ede;
+mod ip;
+mod mail;
+mod owned;
+mod pool;
+mod present;
+mod random;
+mod reverse;
+mod rfc6724;
+#[cfg(feature = "serde")]
+mod rfc8427;
+#[cfg(feature = "rustls")]
+mod rustls_verifier;
+mod security;
+#[cfg(test)]
+mod signed_zone;
+mod socket;
+mod spf;
+mod srv;
+mod sshfp;
+mod svcb;
+mod timeout;
+mod wire;
+
+pub use caa::{Caa, CaaCheck};
+pub use channel::{Query, ResultSender};
+pub use coalesce::Coalescer;
+pub use dane::{
+    set_dane_verify, verify_tlsa, Tlsa, USAGE_DANE_EE, USAGE_DANE_TA, USAGE_PKIX_EE, USAGE_PKIX_TA,
+};
+pub use dkim::{DkimKey, LookupDkim};
+pub use dmarc::{Alignment, Dmarc, DmarcPolicy, LookupDmarc};
+pub use dnsbl::{DnsblResult, DnsblStatus};
+pub use dnssd::ServiceInstance;
+pub use ede::{ExtendedError, InfoCode};
+pub use ip::{IpStrategy, LookupIp, LookupIpIter};
+pub use mail::{MailExchanger, MailExchangers};
+pub use owned::{OwnedAnswer, OwnedDataIter};
+pub use pool::{ResolveFuture, ResolverPool};
+pub use reverse::{reverse_name, LookupPtr};
+#[cfg(feature = "rustls")]
+pub use rustls_verifier::{DaneVerifier, Fallback};
+pub use security::{BogusCategory, BogusReason, Security};
+pub use socket::HostPort;
+pub use spf::{SpfCheck, SpfResult};
+pub use srv::{LookupSrv, SrvTarget};
+pub use sshfp::{LookupSshfp, Sshfp};
+pub use svcb::{HttpsEndpoint, LookupHttps, Svcb};
+
 const IP_CSTR_MAX: usize = 40;
 
 /// Common Result type for operations.
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// Common Error type for operations.
+#[derive(Clone, PartialEq, Eq)]
 pub enum Error {
     /// Argument contained a null byte
     NullByte,
@@ -40,6 +108,18 @@ pub enum Error {
     UB(c_int),
     /// Argument contained invalid UTF8
     UTF8,
+    /// Query was cancelled before an answer was delivered
+    Cancelled,
+    /// Context was dropped before an answer was delivered
+    ContextClosed,
+    /// Deadline passed before an answer was delivered
+    TimedOut,
+    /// Answer was not DNSSEC secure where a secure answer was required
+    Insecure,
+    /// Answer failed DNSSEC validation
+    Bogus,
+    /// Query failed with an RCODE other than NOERROR or NXDOMAIN
+    Rcode(u16),
 }
 
 impl Error {
@@ -54,6 +134,12 @@ impl Error {
                 }
             }
             Error::UTF8 => "argument is invalid UTF-8",
+            Error::Cancelled => "query cancelled",
+            Error::ContextClosed => "context closed",
+            Error::TimedOut => "timed out",
+            Error::Insecure => "answer is not DNSSEC secure",
+            Error::Bogus => "answer failed DNSSEC validation",
+            Error::Rcode(n) => present::rcode_name(n).unwrap_or("query failed"),
         }
     }
 }
@@ -76,6 +162,18 @@ impl std::error::Error for Error {
     }
 }
 
+impl std::convert::From<Error> for std::io::Error {
+    fn from(err: Error) -> std::io::Error {
+        let kind = match err {
+            Error::NullByte | Error::UTF8 => std::io::ErrorKind::InvalidInput,
+            Error::TimedOut => std::io::ErrorKind::TimedOut,
+            Error::Insecure | Error::Bogus => std::io::ErrorKind::InvalidData,
+            _ => std::io::ErrorKind::Other,
+        };
+        std::io::Error::new(kind, err)
+    }
+}
+
 impl std::convert::From<NulError> for Error {
     fn from(_err: NulError) -> Error {
         Error::NullByte
@@ -107,6 +205,13 @@ impl Drop for Answer {
 }
 
 impl Answer {
+    // Fail if the query failed with an RCODE other than NOERROR or NXDOMAIN.
+    pub(crate) fn checked(self) -> Result<Answer> {
+        match self.rcode() {
+            0 | 3 => Ok(self),
+            rcode => Err(Error::Rcode(rcode)),
+        }
+    }
     /// Returns original question's name.
     pub fn qname(&self) -> &str {
         unsafe {
@@ -186,6 +291,16 @@ impl Answer {
     pub fn ttl(&self) -> u32 {
         unsafe { (*self.0).ttl as u32 }
     }
+    /// Returns the outcome of DNSSEC validation.
+    pub fn security(&self) -> Security {
+        Security::from_fields(self.secure(), self.bogus(), self.why_bogus(), self.rcode())
+    }
+    /// Returns the Extended DNS Errors
+    /// ([RFC 8914](https://tools.ietf.org/html/rfc8914)) carried in the answer
+    /// packet. libunbound only includes these when configured with `ede: yes`.
+    pub fn extended_errors(&self) -> Vec<ExtendedError> {
+        ede::from_packet(self.answer())
+    }
 }
 
 impl fmt::Debug for Answer {
@@ -200,6 +315,14 @@ impl fmt::Debug for Answer {
     }
 }
 
+/// Formats the answer like `dig`: the header, question and sections of the answer
+/// packet in presentation format along with the result of DNSSEC validation.
+impl fmt::Display for Answer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        present::write_answer(f, &OwnedAnswer::from(self))
+    }
+}
+
 /// An iterator over the datas of an [Answer](struct.Answer.html).
 pub struct DataIter<'a> {
     index: isize,
@@ -210,12 +333,13 @@ impl<'a> std::iter::Iterator for DataIter<'a> {
     type Item = &'a [u8];
     fn next(&mut self) -> Option<&'a [u8]> {
         let item = unsafe {
-            let offset = (*self.answer.0).data.offset(self.index);
-            if offset.is_null() {
+            // data is terminated by a null pointer.
+            let ptr = *(*self.answer.0).data.offset(self.index);
+            if ptr.is_null() {
                 None
             } else {
                 let len = *(*self.answer.0).len.offset(self.index) as usize;
-                Some(std::slice::from_raw_parts(*offset as *const u8, len))
+                Some(std::slice::from_raw_parts(ptr as *const u8, len))
             }
         };
         if item.is_some() {
@@ -228,6 +352,7 @@ impl<'a> std::iter::Iterator for DataIter<'a> {
 struct Callback {
     async_id: AsyncID,
     ub_id: c_int,
+    deadline: Option<Instant>,
     f: Box<Fn(AsyncID, Result<Answer>) + 'static>,
 }
 
@@ -245,6 +370,16 @@ impl Drop for Context {
         unsafe {
             sys::ub_ctx_delete(self.ub_ctx);
         }
+        let callbacks = {
+            let p = self.protected.get_mut().expect("drop acquire protected");
+            if p.discard_pending {
+                return;
+            }
+            std::mem::take(&mut p.callbacks)
+        };
+        for cb in callbacks {
+            (cb.f)(cb.async_id, Err(Error::ContextClosed));
+        }
     }
 }
 
@@ -433,6 +568,8 @@ impl Context {
         unsafe { sys::ub_fd(self.ub_ctx) }
     }
     /// Process results from the resolver (when `fd` is readable).
+    /// Queries whose deadline has passed are cancelled and their callbacks called
+    /// with `Error::TimedOut`.
     pub fn process(&self) -> Result<()> {
         unsafe {
             CONTEXT_PTR.with(|cell| *cell.get() = &self.protected);
@@ -442,6 +579,7 @@ impl Context {
                 .lock()
                 .expect("process acquire protected")
                 .adjust_capacity();
+            self.expire_deadlines();
             into_result!(ub_err)
         }
     }
@@ -470,6 +608,19 @@ impl Context {
         class: u16,
         callback: C,
     ) -> Result<AsyncID>
+    where
+        C: Fn(AsyncID, Result<Answer>) + 'static,
+    {
+        self.resolve_async_imp(name, rrtype, class, None, callback)
+    }
+    fn resolve_async_imp<C>(
+        &self,
+        name: &str,
+        rrtype: u16,
+        class: u16,
+        deadline: Option<Instant>,
+        callback: C,
+    ) -> Result<AsyncID>
     where
         C: Fn(AsyncID, Result<Answer>) + 'static,
     {
@@ -493,20 +644,50 @@ impl Context {
             );
             let result = into_result!(ub_err, async_id);
             if result.is_ok() {
-                p.callbacks.push(Callback { async_id, ub_id, f });
+                p.callbacks.push(Callback {
+                    async_id,
+                    ub_id,
+                    deadline,
+                    f,
+                });
             }
             result
         }
     }
     /// Cancel an asynchronous query.
+    /// Unless disabled with `notify_pending`, the query's callback is called with
+    /// `Error::Cancelled`.
     pub fn cancel(&self, id: AsyncID) {
-        let mut p = self.protected.lock().expect("cancel acquire protected");
-        if let Some(i) = p.callbacks.iter().position(|c| c.async_id == id) {
-            let ctx = p.callbacks.swap_remove(i);
-            unsafe { sys::ub_cancel(self.ub_ctx, ctx.ub_id) };
-            p.adjust_capacity();
+        let cb = {
+            let mut p = self.protected.lock().expect("cancel acquire protected");
+            match p.callbacks.iter().position(|c| c.async_id == id) {
+                Some(i) => {
+                    let cb = p.callbacks.swap_remove(i);
+                    unsafe { sys::ub_cancel(self.ub_ctx, cb.ub_id) };
+                    p.adjust_capacity();
+                    if p.discard_pending {
+                        None
+                    } else {
+                        Some(cb)
+                    }
+                }
+                None => None,
+            }
+        };
+        if let Some(cb) = cb {
+            (cb.f)(id, Err(Error::Cancelled));
         }
     }
+    /// Set whether callbacks of pending asynchronous queries are called with
+    /// `Error::Cancelled` when the query is cancelled and `Error::ContextClosed`
+    /// when the `Context` is dropped. Enabled by default; when disabled such
+    /// callbacks are discarded without being called.
+    pub fn notify_pending(&self, notify: bool) {
+        self.protected
+            .lock()
+            .expect("notify_pending acquire protected")
+            .discard_pending = !notify;
+    }
     /// Print the local zone information to debug output.
     pub fn print_local_zones(&self) -> Result<()> {
         unsafe { into_result!(sys::ub_ctx_print_local_zones(self.ub_ctx)) }
@@ -569,6 +750,8 @@ impl mio::Evented for Context {
 struct ContextProtected {
     id: usize,
     callbacks: Vec<Callback>,
+    discard_pending: bool,
+    lookup_timeout: Option<Duration>,
 }
 
 impl ContextProtected {
@@ -681,3 +864,47 @@ fn test_move_context() {
     drop(b);
     a.wait().unwrap();
 }
+
+#[test]
+fn test_cancel_notifies_pending() {
+    use std::sync::mpsc;
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let cancelled = {
+        let tx = tx.clone();
+        ctx.resolve_async("localhost", 1, 1, move |id, r| {
+            tx.send((id, r.err())).unwrap()
+        })
+        .unwrap()
+    };
+    let closed = ctx
+        .resolve_async("localhost", 28, 1, move |id, r| {
+            tx.send((id, r.err())).unwrap()
+        })
+        .unwrap();
+    ctx.cancel(cancelled);
+    match rx.try_recv().unwrap() {
+        (id, Some(Error::Cancelled)) => assert_eq!(id, cancelled),
+        other => panic!("unexpected {:?}", other),
+    }
+    drop(ctx);
+    match rx.try_recv().unwrap() {
+        (id, Some(Error::ContextClosed)) => assert_eq!(id, closed),
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn test_discard_pending() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    ctx.notify_pending(false);
+    let id = ctx
+        .resolve_async("localhost", 1, 1, |_, _| panic!("callback called"))
+        .unwrap();
+    ctx.resolve_async("localhost", 28, 1, |_, _| panic!("callback called"))
+        .unwrap();
+    ctx.cancel(id);
+    drop(ctx);
+}
@@ -0,0 +1,185 @@
+//! Mail exchanger lookups.
+use super::random;
+use super::wire;
+use super::{ip, Context, LookupIp, Result, Security};
+
+/// A mail exchanger for a domain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MailExchanger {
+    preference: u16,
+    exchange: String,
+    addrs: Result<LookupIp>,
+}
+
+impl MailExchanger {
+    /// Returns the preference of the exchanger. Lower values are preferred.
+    pub fn preference(&self) -> u16 {
+        self.preference
+    }
+    /// Returns the name of the exchanger in presentation format with a trailing dot.
+    pub fn exchange(&self) -> &str {
+        &self.exchange
+    }
+    /// Returns the addresses of the exchanger, or the error that prevented them
+    /// being looked up.
+    pub fn addrs(&self) -> Result<&LookupIp> {
+        self.addrs.as_ref().map_err(Clone::clone)
+    }
+}
+
+/// The mail exchangers of a domain, as returned by
+/// [Context::lookup_mail_exchangers](struct.Context.html#method.lookup_mail_exchangers).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MailExchangers {
+    exchangers: Vec<MailExchanger>,
+    null: bool,
+    implicit: bool,
+    nxdomain: bool,
+    security: Security,
+}
+
+impl MailExchangers {
+    /// Returns the exchangers in the order delivery should be attempted.
+    pub fn exchangers(&self) -> &[MailExchanger] {
+        &self.exchangers
+    }
+    /// Returns true if the domain publishes a null MX
+    /// ([RFC 7505](https://tools.ietf.org/html/rfc7505)) and does not accept mail.
+    pub fn is_null(&self) -> bool {
+        self.null
+    }
+    /// Returns true if the domain has no MX records and the domain itself is used
+    /// as an implicit exchanger as described in
+    /// [RFC 5321](https://tools.ietf.org/html/rfc5321#section-5.1).
+    pub fn is_implicit(&self) -> bool {
+        self.implicit
+    }
+    /// Returns true if the domain doesn't exist, in which case there are no
+    /// exchangers.
+    pub fn is_nxdomain(&self) -> bool {
+        self.nxdomain
+    }
+    /// Returns the outcome of DNSSEC validation of the MX query.
+    pub fn security(&self) -> &Security {
+        &self.security
+    }
+}
+
+impl Context {
+    /// Look up where mail for a domain should be delivered.
+    ///
+    /// Exchangers are ordered by preference with ties broken randomly. If the
+    /// domain exists but has no MX records the domain itself is returned as the
+    /// sole exchanger; if it doesn't exist there are no exchangers and
+    /// `is_nxdomain` returns true. The addresses of the exchangers are looked up
+    /// concurrently.
+    pub fn lookup_mail_exchangers(&self, domain: &str) -> Result<MailExchangers> {
+        let answer = self.resolve(domain, 15, 1)?.checked()?;
+        let security = answer.security();
+        let nxdomain = answer.nxdomain();
+        let mut mx: Vec<(u16, String)> = answer
+            .data()
+            .filter_map(|data| {
+                let preference = wire::read_u16(data, 0)?;
+                let (exchange, _) = wire::read_rdata_name(data, 2)?;
+                Some((preference, exchange))
+            })
+            .collect();
+        if mx.iter().any(|(_, exchange)| exchange == ".") {
+            return Ok(MailExchangers {
+                exchangers: Vec::new(),
+                null: true,
+                implicit: false,
+                nxdomain,
+                security,
+            });
+        }
+        let implicit = mx.is_empty() && !nxdomain;
+        if implicit {
+            let mut exchange = domain.to_owned();
+            if !exchange.ends_with('.') {
+                exchange.push('.');
+            }
+            mx.push((0, exchange));
+        }
+        random::shuffle(&mut mx);
+        mx.sort_by_key(|&(preference, _)| preference);
+        let queries: Vec<_> = mx
+            .iter()
+            .flat_map(|(_, exchange)| vec![(&exchange[..], 28, 1), (&exchange[..], 1, 1)])
+            .collect();
+        let mut results = self.resolve_all(&queries)?.into_iter();
+        let exchangers = mx
+            .into_iter()
+            .map(|(preference, exchange)| {
+                let answers: Vec<_> = results.by_ref().take(2).collect();
+                MailExchanger {
+                    preference,
+                    exchange,
+                    addrs: ip::from_answers([28, 1].iter().cloned().zip(answers)),
+                }
+            })
+            .collect();
+        Ok(MailExchangers {
+            exchangers,
+            null: false,
+            implicit,
+            nxdomain,
+            security,
+        })
+    }
+}
+
+#[test]
+fn test_lookup_mail_exchangers() {
+    use std::net::IpAddr;
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    ctx.zone_add("mx.test.", "static").unwrap();
+    ctx.data_add("a.mx.test. MX 20 mx2.mx.test.").unwrap();
+    ctx.data_add("a.mx.test. MX 10 mx1.mx.test.").unwrap();
+    ctx.data_add("mx1.mx.test. A 192.0.2.1").unwrap();
+    ctx.data_add("null.mx.test. MX 0 .").unwrap();
+    ctx.data_add("implicit.mx.test. AAAA 2001:db8::1").unwrap();
+    let mx = ctx.lookup_mail_exchangers("a.mx.test").unwrap();
+    let names: Vec<_> = mx.exchangers().iter().map(|e| e.exchange()).collect();
+    assert_eq!(names, ["mx1.mx.test.", "mx2.mx.test."]);
+    let addrs: Vec<_> = mx.exchangers()[0].addrs().unwrap().iter().collect();
+    assert_eq!(addrs, ["192.0.2.1".parse::<IpAddr>().unwrap()]);
+    assert!(mx.exchangers()[1].addrs().unwrap().is_empty());
+    assert!(!mx.is_null() && !mx.is_implicit());
+    assert!(ctx
+        .lookup_mail_exchangers("null.mx.test")
+        .unwrap()
+        .is_null());
+    let mx = ctx.lookup_mail_exchangers("implicit.mx.test").unwrap();
+    assert!(mx.is_implicit() && !mx.is_nxdomain());
+    assert_eq!(mx.exchangers()[0].exchange(), "implicit.mx.test.");
+    let mx = ctx.lookup_mail_exchangers("missing.mx.test").unwrap();
+    assert!(mx.exchangers().is_empty());
+    assert!(mx.is_nxdomain() && !mx.is_implicit());
+}
+
+#[test]
+fn test_lookup_mail_exchangers_partial() {
+    use super::Error;
+    use std::time::Duration;
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    ctx.set_fwd4(std::net::Ipv4Addr::new(192, 0, 2, 1)).unwrap();
+    ctx.set_lookup_timeout(Duration::from_millis(100));
+    ctx.zone_add("mx.test.", "typetransparent").unwrap();
+    ctx.data_add("a.mx.test. MX 10 mx1.mx.test.").unwrap();
+    ctx.data_add("a.mx.test. MX 20 mx2.mx.test.").unwrap();
+    ctx.data_add("mx1.mx.test. A 192.0.2.1").unwrap();
+    ctx.data_add("mx2.mx.test. A 192.0.2.2").unwrap();
+    ctx.data_add("mx2.mx.test. AAAA 2001:db8::2").unwrap();
+    let mx = ctx.lookup_mail_exchangers("a.mx.test").unwrap();
+    let first = mx.exchangers()[0].addrs().unwrap();
+    assert_eq!(first.ipv6_error(), Some(&Error::TimedOut));
+    assert_eq!(first.iter().count(), 1);
+    let second = mx.exchangers()[1].addrs().unwrap();
+    assert_eq!(second.ipv4_error(), None);
+    assert_eq!(second.ipv6_error(), None);
+    assert_eq!(second.iter().count(), 2);
+}
@@ -0,0 +1,173 @@
+//! Owned copies of answers.
+use std::sync::Arc;
+use std::{fmt, slice};
+
+use super::{ede, present, Answer, ExtendedError, Security};
+
+/// An owned copy of an [Answer](struct.Answer.html).
+///
+/// Unlike `Answer`, which borrows from memory allocated by libunbound, an
+/// `OwnedAnswer` holds its fields in reference counted Rust memory. Cloning is
+/// cheap and clones compare equal.
+///
+/// With the `serde` feature enabled `OwnedAnswer` can be serialized and
+/// deserialized as an [RFC 8427](https://tools.ietf.org/html/rfc8427) JSON
+/// object. `Answer` can be serialized the same way.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct OwnedAnswer(Arc<Fields>);
+
+#[derive(PartialEq, Eq, Hash)]
+pub(crate) struct Fields {
+    pub(crate) qname: String,
+    pub(crate) qtype: u16,
+    pub(crate) qclass: u16,
+    pub(crate) data: Vec<Vec<u8>>,
+    pub(crate) canonname: Option<String>,
+    pub(crate) rcode: u16,
+    pub(crate) answer: Option<Vec<u8>>,
+    pub(crate) havedata: bool,
+    pub(crate) nxdomain: bool,
+    pub(crate) secure: bool,
+    pub(crate) bogus: bool,
+    pub(crate) why_bogus: Option<String>,
+    pub(crate) ttl: u32,
+}
+
+impl OwnedAnswer {
+    pub(crate) fn from_fields(fields: Fields) -> OwnedAnswer {
+        OwnedAnswer(Arc::new(fields))
+    }
+    /// Returns original question's name.
+    pub fn qname(&self) -> &str {
+        &self.0.qname
+    }
+    /// Returns original question's qtype.
+    pub fn qtype(&self) -> u16 {
+        self.0.qtype
+    }
+    /// Returns original question's qclass.
+    pub fn qclass(&self) -> u16 {
+        self.0.qclass
+    }
+    /// Returns an iterator over answer record datas.
+    pub fn data(&self) -> OwnedDataIter<'_> {
+        OwnedDataIter(self.0.data.iter())
+    }
+    /// Returns canonical name of result, if any.
+    pub fn canonname(&self) -> Option<&str> {
+        self.0.canonname.as_deref()
+    }
+    /// Returns additional error code in case of no data.
+    pub fn rcode(&self) -> u16 {
+        self.0.rcode
+    }
+    /// Returns answer packet, if any.
+    pub fn answer(&self) -> Option<&[u8]> {
+        self.0.answer.as_deref()
+    }
+    /// Returns true if there is data.
+    pub fn havedata(&self) -> bool {
+        self.0.havedata
+    }
+    /// Returns true if there is no data because a name does not exist.
+    pub fn nxdomain(&self) -> bool {
+        self.0.nxdomain
+    }
+    /// True if result is secure.
+    pub fn secure(&self) -> bool {
+        self.0.secure
+    }
+    /// True if a security failure happened.
+    pub fn bogus(&self) -> bool {
+        self.0.bogus
+    }
+    /// String error if response is bogus.
+    pub fn why_bogus(&self) -> Option<&str> {
+        self.0.why_bogus.as_deref()
+    }
+    /// Number of seconds the result is valid.
+    pub fn ttl(&self) -> u32 {
+        self.0.ttl
+    }
+    /// Returns the outcome of DNSSEC validation.
+    pub fn security(&self) -> Security {
+        Security::from_fields(self.secure(), self.bogus(), self.why_bogus(), self.rcode())
+    }
+    /// Returns the Extended DNS Errors carried in the answer packet.
+    pub fn extended_errors(&self) -> Vec<ExtendedError> {
+        ede::from_packet(self.answer())
+    }
+}
+
+impl<'a> From<&'a Answer> for OwnedAnswer {
+    fn from(answer: &'a Answer) -> OwnedAnswer {
+        OwnedAnswer::from_fields(Fields {
+            qname: answer.qname().to_owned(),
+            qtype: answer.qtype(),
+            qclass: answer.qclass(),
+            data: answer.data().map(<[u8]>::to_vec).collect(),
+            canonname: answer.canonname().map(str::to_owned),
+            rcode: answer.rcode(),
+            answer: answer.answer().map(<[u8]>::to_vec),
+            havedata: answer.havedata(),
+            nxdomain: answer.nxdomain(),
+            secure: answer.secure(),
+            bogus: answer.bogus(),
+            why_bogus: answer.why_bogus().map(str::to_owned),
+            ttl: answer.ttl(),
+        })
+    }
+}
+
+impl From<Answer> for OwnedAnswer {
+    fn from(answer: Answer) -> OwnedAnswer {
+        OwnedAnswer::from(&answer)
+    }
+}
+
+impl fmt::Debug for OwnedAnswer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_fmt(format_args!(
+            "OwnedAnswer({:p}/{}/{}/{})",
+            self.0,
+            self.qname(),
+            self.qtype(),
+            self.qclass()
+        ))
+    }
+}
+
+/// Formats the answer like `dig`. See [Answer](struct.Answer.html).
+impl fmt::Display for OwnedAnswer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        present::write_answer(f, self)
+    }
+}
+
+/// An iterator over the datas of an [OwnedAnswer](struct.OwnedAnswer.html).
+#[derive(Clone, Debug)]
+pub struct OwnedDataIter<'a>(slice::Iter<'a, Vec<u8>>);
+
+impl<'a> Iterator for OwnedDataIter<'a> {
+    type Item = &'a [u8];
+    fn next(&mut self) -> Option<&'a [u8]> {
+        self.0.next().map(Vec::as_slice)
+    }
+}
+
+#[test]
+fn test_owned_answer() {
+    use super::Context;
+    let ctx = Context::new().unwrap();
+    let answer = ctx.resolve("localhost", 1, 1).unwrap();
+    let owned = OwnedAnswer::from(&answer);
+    assert_eq!(owned.qname(), answer.qname());
+    assert_eq!(owned.qtype(), answer.qtype());
+    assert!(owned.data().eq(answer.data()));
+    assert_eq!(owned.answer(), answer.answer());
+    assert_eq!(owned.why_bogus(), answer.why_bogus());
+    let clone = owned.clone();
+    drop(answer);
+    assert_eq!(clone, owned);
+    assert_eq!(clone.data().next(), Some(&[127, 0, 0, 1][..]));
+}
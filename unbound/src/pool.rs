@@ -0,0 +1,233 @@
+//! A pool of threads that make blocking `resolve` calls on behalf of asynchronous code.
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context as TaskContext, Poll, Waker};
+use std::thread;
+
+use super::{Answer, Context, Error, Query, Result};
+
+/// Runs `Context::resolve` on a fixed number of worker threads, exposing each
+/// resolution as a `Future`.
+///
+/// At most `concurrency` queries are resolved at once; further queries wait in a
+/// queue. Dropping the pool completes queued queries with `Error::Cancelled` and
+/// waits for queries being resolved to finish.
+pub struct ResolverPool {
+    shared: Arc<Shared>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+struct Shared {
+    ctx: Arc<Context>,
+    state: Mutex<PoolState>,
+    cond: Condvar,
+}
+
+#[derive(Default)]
+struct PoolState {
+    queue: VecDeque<Job>,
+    active: usize,
+    shutdown: bool,
+}
+
+struct Job {
+    query: Query,
+    slot: Arc<Mutex<Slot>>,
+}
+
+#[derive(Default)]
+struct Slot {
+    result: Option<Result<Answer>>,
+    waker: Option<Waker>,
+}
+
+impl Slot {
+    fn complete(slot: &Mutex<Slot>, result: Result<Answer>) {
+        let waker = {
+            let mut slot = slot.lock().expect("complete acquire slot");
+            slot.result = Some(result);
+            slot.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl ResolverPool {
+    /// Create a pool of `concurrency` threads resolving queries with `ctx`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `concurrency` is zero.
+    pub fn new(ctx: Arc<Context>, concurrency: usize) -> ResolverPool {
+        assert!(concurrency > 0, "concurrency must be greater than zero");
+        let shared = Arc::new(Shared {
+            ctx,
+            state: Mutex::new(Default::default()),
+            cond: Condvar::new(),
+        });
+        let workers = (0..concurrency)
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || shared.work())
+            })
+            .collect();
+        ResolverPool { shared, workers }
+    }
+    /// Returns the `Context` queries are resolved with.
+    pub fn context(&self) -> &Arc<Context> {
+        &self.shared.ctx
+    }
+    /// Queue a query to be resolved and validated. Dropping the returned future
+    /// before its query has started prevents it from being resolved.
+    pub fn resolve(&self, name: &str, rrtype: u16, class: u16) -> ResolveFuture {
+        let slot = Arc::new(Mutex::new(Slot::default()));
+        let job = Job {
+            query: Query::new(name, rrtype, class),
+            slot: slot.clone(),
+        };
+        self.shared
+            .state
+            .lock()
+            .expect("resolve acquire state")
+            .queue
+            .push_back(job);
+        self.shared.cond.notify_one();
+        ResolveFuture { slot }
+    }
+    /// Returns the number of queries waiting for a worker.
+    pub fn queue_depth(&self) -> usize {
+        self.shared
+            .state
+            .lock()
+            .expect("queue_depth acquire state")
+            .queue
+            .len()
+    }
+    /// Returns the number of queries currently being resolved.
+    pub fn active(&self) -> usize {
+        self.shared
+            .state
+            .lock()
+            .expect("active acquire state")
+            .active
+    }
+    /// Returns the maximum number of queries resolved at once.
+    pub fn concurrency(&self) -> usize {
+        self.workers.len()
+    }
+}
+
+impl Drop for ResolverPool {
+    fn drop(&mut self) {
+        let queue = {
+            let mut state = self.shared.state.lock().expect("drop acquire state");
+            state.shutdown = true;
+            std::mem::take(&mut state.queue)
+        };
+        self.shared.cond.notify_all();
+        for job in queue {
+            Slot::complete(&job.slot, Err(Error::Cancelled));
+        }
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for ResolverPool {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "ResolverPool({:p}/{})",
+            self,
+            self.concurrency()
+        ))
+    }
+}
+
+impl Shared {
+    fn work(&self) {
+        loop {
+            let job = {
+                let mut state = self.state.lock().expect("work acquire state");
+                loop {
+                    if state.shutdown {
+                        return;
+                    }
+                    match state.queue.pop_front() {
+                        // Nobody is waiting on the result.
+                        Some(ref job) if Arc::strong_count(&job.slot) == 1 => continue,
+                        Some(job) => {
+                            state.active += 1;
+                            break job;
+                        }
+                        None => state = self.cond.wait(state).expect("work wait state"),
+                    }
+                }
+            };
+            let q = &job.query;
+            let result = self.ctx.resolve(&q.name, q.rrtype, q.class);
+            self.state.lock().expect("work acquire state").active -= 1;
+            Slot::complete(&job.slot, result);
+        }
+    }
+}
+
+/// The future result of [ResolverPool::resolve](struct.ResolverPool.html#method.resolve).
+pub struct ResolveFuture {
+    slot: Arc<Mutex<Slot>>,
+}
+
+impl Future for ResolveFuture {
+    type Output = Result<Answer>;
+    fn poll(self: Pin<&mut Self>, cx: &mut TaskContext) -> Poll<Result<Answer>> {
+        let mut slot = self.slot.lock().expect("poll acquire slot");
+        match slot.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for ResolveFuture {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_fmt(format_args!("ResolveFuture({:p})", self))
+    }
+}
+
+#[test]
+fn test_resolver_pool() {
+    use std::task::Wake;
+
+    struct ThreadWaker(thread::Thread);
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark()
+        }
+    }
+    fn block_on<F: Future>(mut f: F) -> F::Output {
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut cx = TaskContext::from_waker(&waker);
+        let mut f = unsafe { Pin::new_unchecked(&mut f) };
+        loop {
+            if let Poll::Ready(output) = f.as_mut().poll(&mut cx) {
+                return output;
+            }
+            thread::park();
+        }
+    }
+
+    let pool = ResolverPool::new(Arc::new(Context::new().unwrap()), 2);
+    assert_eq!(pool.concurrency(), 2);
+    let futures: Vec<_> = (0..4).map(|_| pool.resolve("localhost", 1, 1)).collect();
+    for f in futures {
+        assert!(block_on(f).unwrap().havedata());
+    }
+    assert_eq!(pool.queue_depth(), 0);
+}
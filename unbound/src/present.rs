@@ -0,0 +1,571 @@
+//! Presentation format (RFC 1035 master file syntax) of messages and record data.
+use std::fmt::{self, Write};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use super::wire::{self, Message, Record};
+use super::{ede, OwnedAnswer};
+
+/// Returns the mnemonic for a record type, if known.
+pub(crate) fn type_name(rrtype: u16) -> Option<&'static str> {
+    Some(match rrtype {
+        1 => "A",
+        2 => "NS",
+        5 => "CNAME",
+        6 => "SOA",
+        12 => "PTR",
+        13 => "HINFO",
+        15 => "MX",
+        16 => "TXT",
+        28 => "AAAA",
+        33 => "SRV",
+        35 => "NAPTR",
+        39 => "DNAME",
+        41 => "OPT",
+        43 => "DS",
+        44 => "SSHFP",
+        46 => "RRSIG",
+        47 => "NSEC",
+        48 => "DNSKEY",
+        50 => "NSEC3",
+        51 => "NSEC3PARAM",
+        52 => "TLSA",
+        53 => "SMIMEA",
+        59 => "CDS",
+        60 => "CDNSKEY",
+        61 => "OPENPGPKEY",
+        64 => "SVCB",
+        65 => "HTTPS",
+        99 => "SPF",
+        255 => "ANY",
+        257 => "CAA",
+        _ => return None,
+    })
+}
+
+/// Formats a record type as its mnemonic or in RFC 3597 `TYPEn` form.
+pub(crate) struct Type(pub(crate) u16);
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match type_name(self.0) {
+            Some(name) => f.write_str(name),
+            None => write!(f, "TYPE{}", self.0),
+        }
+    }
+}
+
+/// Formats a class as its mnemonic or in RFC 3597 `CLASSn` form.
+pub(crate) struct Class(pub(crate) u16);
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            1 => f.write_str("IN"),
+            3 => f.write_str("CH"),
+            4 => f.write_str("HS"),
+            254 => f.write_str("NONE"),
+            255 => f.write_str("ANY"),
+            n => write!(f, "CLASS{}", n),
+        }
+    }
+}
+
+pub(crate) fn rcode_name(rcode: u16) -> Option<&'static str> {
+    Some(match rcode {
+        0 => "NOERROR",
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        3 => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        6 => "YXDOMAIN",
+        7 => "YXRRSET",
+        8 => "NXRRSET",
+        9 => "NOTAUTH",
+        10 => "NOTZONE",
+        _ => return None,
+    })
+}
+
+fn opcode_name(opcode: u8) -> Option<&'static str> {
+    Some(match opcode {
+        0 => "QUERY",
+        1 => "IQUERY",
+        2 => "STATUS",
+        4 => "NOTIFY",
+        5 => "UPDATE",
+        _ => return None,
+    })
+}
+
+/// Formats record data found at `start` in `msg`, which ends where the data ends.
+/// Names within the data may be compressed. Data of unknown types or that can't
+/// be decoded is formatted in RFC 3597 `\# len hex` form.
+pub(crate) struct Rdata<'a> {
+    pub(crate) rrtype: u16,
+    pub(crate) msg: &'a [u8],
+    pub(crate) start: usize,
+}
+
+impl<'a> fmt::Display for Rdata<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match known_rdata(self.rrtype, self.msg, self.start) {
+            Some(s) => f.write_str(&s),
+            None => {
+                let rdata = &self.msg[self.start..];
+                write!(f, "\\# {}", rdata.len())?;
+                if !rdata.is_empty() {
+                    write!(f, " {}", wire::hex(rdata))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// A cursor over record data that may refer to earlier parts of the message.
+struct Cursor<'a> {
+    msg: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn u8(&mut self) -> Option<u8> {
+        let b = *self.msg.get(self.pos)?;
+        self.pos += 1;
+        Some(b)
+    }
+    fn u16(&mut self) -> Option<u16> {
+        let n = wire::read_u16(self.msg, self.pos)?;
+        self.pos += 2;
+        Some(n)
+    }
+    fn u32(&mut self) -> Option<u32> {
+        let n = wire::read_u32(self.msg, self.pos)?;
+        self.pos += 4;
+        Some(n)
+    }
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let b = self.msg.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(b)
+    }
+    fn rest(&mut self) -> &'a [u8] {
+        let b = &self.msg[self.pos..];
+        self.pos = self.msg.len();
+        b
+    }
+    fn name(&mut self) -> Option<String> {
+        let (name, next) = wire::read_name(self.msg, self.pos)?;
+        self.pos = next;
+        Some(name)
+    }
+    fn string(&mut self) -> Option<String> {
+        let len = self.u8()? as usize;
+        Some(quote(self.bytes(len)?))
+    }
+    fn done(&self) -> bool {
+        self.pos == self.msg.len()
+    }
+}
+
+fn known_rdata(rrtype: u16, msg: &[u8], start: usize) -> Option<String> {
+    let mut c = Cursor { msg, pos: start };
+    let mut s = String::new();
+    match rrtype {
+        1 => {
+            let b = c.bytes(4)?;
+            write!(s, "{}", Ipv4Addr::new(b[0], b[1], b[2], b[3])).ok()?;
+        }
+        28 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(c.bytes(16)?);
+            write!(s, "{}", Ipv6Addr::from(octets)).ok()?;
+        }
+        2 | 5 | 12 | 39 => s = c.name()?,
+        6 => {
+            let (mname, rname) = (c.name()?, c.name()?);
+            write!(s, "{} {}", mname, rname).ok()?;
+            for _ in 0..5 {
+                write!(s, " {}", c.u32()?).ok()?;
+            }
+        }
+        13 => write!(s, "{} {}", c.string()?, c.string()?).ok()?,
+        15 => write!(s, "{} {}", c.u16()?, c.name()?).ok()?,
+        16 | 99 => {
+            let mut strings = Vec::new();
+            while !c.done() {
+                strings.push(c.string()?);
+            }
+            s = strings.join(" ");
+        }
+        33 => {
+            let (priority, weight, port) = (c.u16()?, c.u16()?, c.u16()?);
+            write!(s, "{} {} {} {}", priority, weight, port, c.name()?).ok()?;
+        }
+        35 => {
+            let (order, preference) = (c.u16()?, c.u16()?);
+            let (flags, services, regexp) = (c.string()?, c.string()?, c.string()?);
+            write!(
+                s,
+                "{} {} {} {} {} {}",
+                order,
+                preference,
+                flags,
+                services,
+                regexp,
+                c.name()?
+            )
+            .ok()?;
+        }
+        43 | 59 => {
+            let (key_tag, alg, digest_type) = (c.u16()?, c.u8()?, c.u8()?);
+            let digest = wire::hex(c.rest());
+            write!(s, "{} {} {} {}", key_tag, alg, digest_type, digest).ok()?;
+        }
+        44 => {
+            let (alg, fp_type) = (c.u8()?, c.u8()?);
+            write!(s, "{} {} {}", alg, fp_type, wire::hex(c.rest())).ok()?;
+        }
+        46 => {
+            let (covered, alg, labels, ttl) = (c.u16()?, c.u8()?, c.u8()?, c.u32()?);
+            let (expiration, inception, key_tag) = (c.u32()?, c.u32()?, c.u16()?);
+            write!(
+                s,
+                "{} {} {} {} {} {} {} {} {}",
+                Type(covered),
+                alg,
+                labels,
+                ttl,
+                timestamp(expiration),
+                timestamp(inception),
+                key_tag,
+                c.name()?,
+                base64(c.rest())
+            )
+            .ok()?;
+        }
+        47 => {
+            s = c.name()?;
+            type_bitmap(&mut s, c.rest())?;
+        }
+        48 | 60 => {
+            let (flags, protocol, alg) = (c.u16()?, c.u8()?, c.u8()?);
+            write!(s, "{} {} {} {}", flags, protocol, alg, base64(c.rest())).ok()?;
+        }
+        50 | 51 => {
+            let (alg, flags, iterations) = (c.u8()?, c.u8()?, c.u16()?);
+            let salt_len = c.u8()? as usize;
+            let salt = match c.bytes(salt_len)? {
+                [] => "-".to_owned(),
+                b => wire::hex(b),
+            };
+            write!(s, "{} {} {} {}", alg, flags, iterations, salt).ok()?;
+            if rrtype == 50 {
+                let hash_len = c.u8()? as usize;
+                write!(s, " {}", base32hex(c.bytes(hash_len)?)).ok()?;
+                type_bitmap(&mut s, c.rest())?;
+            }
+        }
+        52 | 53 => {
+            let (usage, selector, matching) = (c.u8()?, c.u8()?, c.u8()?);
+            let data = wire::hex(c.rest());
+            write!(s, "{} {} {} {}", usage, selector, matching, data).ok()?;
+        }
+        257 => {
+            let flags = c.u8()?;
+            let tag_len = c.u8()? as usize;
+            let tag = c.bytes(tag_len)?;
+            if tag.is_empty() || !tag.iter().all(u8::is_ascii_alphanumeric) {
+                return None;
+            }
+            let tag = std::str::from_utf8(tag).ok()?;
+            write!(s, "{} {} {}", flags, tag, quote(c.rest())).ok()?;
+        }
+        _ => return None,
+    }
+    if c.done() {
+        Some(s)
+    } else {
+        None
+    }
+}
+
+// Quote a character-string, escaping as needed.
+fn quote(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() + 2);
+    s.push('"');
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => {
+                s.push('\\');
+                s.push(b as char);
+            }
+            0x20..=0x7E => s.push(b as char),
+            _ => s.push_str(&format!("\\{:03}", b)),
+        }
+    }
+    s.push('"');
+    s
+}
+
+// Append the types in an NSEC/NSEC3 type bitmap.
+fn type_bitmap(s: &mut String, mut bitmap: &[u8]) -> Option<()> {
+    while !bitmap.is_empty() {
+        let window = u16::from(*bitmap.first()?);
+        let len = *bitmap.get(1)? as usize;
+        let bits = bitmap.get(2..2 + len)?;
+        for (i, &byte) in bits.iter().enumerate() {
+            for bit in 0..8 {
+                if byte & (0x80 >> bit) != 0 {
+                    let rrtype = window << 8 | (i as u16) << 3 | bit;
+                    write!(s, " {}", Type(rrtype)).ok()?;
+                }
+            }
+        }
+        bitmap = &bitmap[2 + len..];
+    }
+    Some(())
+}
+
+// Format seconds since the epoch as YYYYMMDDHHmmSS (RFC 4034 section 3.2).
+fn timestamp(secs: u32) -> String {
+    let days = i64::from(secs / 86400);
+    let rem = secs % 86400;
+    // Civil from days; see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut s = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | u32::from(b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+fn base32hex(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHIJKLMNOPQRSTUV";
+    let mut s = String::with_capacity((bytes.len() + 4) / 5 * 8);
+    for chunk in bytes.chunks(5) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u64, |n, (i, &b)| n | u64::from(b) << (32 - 8 * i));
+        let digits = (chunk.len() * 8 + 4) / 5;
+        for i in 0..digits {
+            s.push(ALPHABET[(n >> (35 - 5 * i) & 0x1F) as usize] as char);
+        }
+    }
+    s
+}
+
+fn write_record(f: &mut fmt::Formatter, record: &Record) -> fmt::Result {
+    let (msg, start) = record.rdata_in_message();
+    writeln!(
+        f,
+        "{}\t{}\t{}\t{}\t{}",
+        record.name,
+        record.ttl,
+        Class(record.class),
+        Type(record.rrtype),
+        Rdata {
+            rrtype: record.rrtype,
+            msg,
+            start
+        }
+    )
+}
+
+fn write_opt(f: &mut fmt::Formatter, opt: &Record) -> fmt::Result {
+    // The OPT record's TTL field holds the extended RCODE, version and flags.
+    let version = (opt.ttl >> 16) & 0xFF;
+    let flags = if opt.ttl & 0x8000 != 0 { " do" } else { "" };
+    writeln!(f, ";; OPT PSEUDOSECTION:")?;
+    writeln!(
+        f,
+        "; EDNS: version: {}, flags:{}; udp: {}",
+        version, flags, opt.class
+    )?;
+    for error in ede::from_options(opt.rdata()) {
+        writeln!(f, "; EDE: {}", error)?;
+    }
+    Ok(())
+}
+
+fn write_status(f: &mut fmt::Formatter, rcode: u16) -> fmt::Result {
+    match rcode_name(rcode) {
+        Some(name) => f.write_str(name),
+        None => write!(f, "RCODE{}", rcode),
+    }
+}
+
+fn write_dnssec(f: &mut fmt::Formatter, answer: &OwnedAnswer) -> fmt::Result {
+    writeln!(f, ";; DNSSEC: {}", answer.security())
+}
+
+fn write_message(f: &mut fmt::Formatter, answer: &OwnedAnswer, msg: &Message) -> fmt::Result {
+    let h = &msg.header;
+    f.write_str(";; ->>HEADER<<- opcode: ")?;
+    match opcode_name(h.opcode) {
+        Some(name) => f.write_str(name)?,
+        None => write!(f, "OPCODE{}", h.opcode)?,
+    }
+    f.write_str(", status: ")?;
+    write_status(f, u16::from(h.rcode))?;
+    writeln!(f, ", id: {}", h.id)?;
+    f.write_str(";; flags:")?;
+    let flags = [
+        (h.qr, " qr"),
+        (h.aa, " aa"),
+        (h.tc, " tc"),
+        (h.rd, " rd"),
+        (h.ra, " ra"),
+        (h.ad, " ad"),
+        (h.cd, " cd"),
+    ];
+    for &(set, name) in &flags {
+        if set {
+            f.write_str(name)?;
+        }
+    }
+    writeln!(
+        f,
+        "; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}",
+        h.qdcount, h.ancount, h.nscount, h.arcount
+    )?;
+    write_dnssec(f, answer)?;
+    let (opt, additional): (Vec<&Record>, Vec<&Record>) =
+        msg.additional.iter().partition(|r| r.rrtype == 41);
+    for opt in opt {
+        writeln!(f)?;
+        write_opt(f, opt)?;
+    }
+    writeln!(f, "\n;; QUESTION SECTION:")?;
+    for q in &msg.question {
+        writeln!(f, ";{}\t\t{}\t{}", q.name, Class(q.qclass), Type(q.qtype))?;
+    }
+    let sections = [
+        ("ANSWER", msg.answer.iter().collect()),
+        ("AUTHORITY", msg.authority.iter().collect()),
+        ("ADDITIONAL", additional),
+    ];
+    for &(title, ref records) in &sections {
+        if !records.is_empty() {
+            writeln!(f, "\n;; {} SECTION:", title)?;
+            for record in records {
+                write_record(f, record)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Used when libunbound didn't provide a packet (or it couldn't be decoded).
+fn write_fields(f: &mut fmt::Formatter, answer: &OwnedAnswer) -> fmt::Result {
+    f.write_str(";; ->>HEADER<<- status: ")?;
+    write_status(f, answer.rcode())?;
+    writeln!(f)?;
+    write_dnssec(f, answer)?;
+    let mut qname = answer.qname().to_owned();
+    if !qname.ends_with('.') {
+        qname.push('.');
+    }
+    let (qtype, qclass) = (Type(answer.qtype()), Class(answer.qclass()));
+    writeln!(f, "\n;; QUESTION SECTION:")?;
+    writeln!(f, ";{}\t\t{}\t{}", qname, qclass, qtype)?;
+    if answer.havedata() {
+        let owner = answer.canonname().unwrap_or(&qname);
+        writeln!(f, "\n;; ANSWER SECTION:")?;
+        for data in answer.data() {
+            let rdata = Rdata {
+                rrtype: answer.qtype(),
+                msg: data,
+                start: 0,
+            };
+            let ttl = answer.ttl();
+            writeln!(f, "{}\t{}\t{}\t{}\t{}", owner, ttl, qclass, qtype, rdata)?;
+        }
+    }
+    Ok(())
+}
+
+/// Formats an answer like the output of `dig`.
+pub(crate) fn write_answer(f: &mut fmt::Formatter, answer: &OwnedAnswer) -> fmt::Result {
+    match answer.answer().and_then(Message::parse) {
+        Some(msg) => write_message(f, answer, &msg),
+        None => write_fields(f, answer),
+    }
+}
+
+#[test]
+fn test_rdata() {
+    let fmt = |rrtype, rdata: &[u8]| {
+        Rdata {
+            rrtype,
+            msg: rdata,
+            start: 0,
+        }
+        .to_string()
+    };
+    assert_eq!(fmt(1, &[192, 0, 2, 1]), "192.0.2.1");
+    assert_eq!(
+        fmt(15, b"\x00\x0a\x04mail\x07example\x00"),
+        "10 mail.example."
+    );
+    assert_eq!(fmt(16, b"\x05a \"b\\\x01c"), "\"a \\\"b\\\\\" \"c\"");
+    assert_eq!(
+        fmt(257, b"\x80\x05issueca.example"),
+        "128 issue \"ca.example\""
+    );
+    assert_eq!(
+        fmt(47, b"\x00\x00\x07\x62\x01\x80\x08\x00\x03\x80"),
+        ". A NS SOA MX TXT AAAA RRSIG NSEC DNSKEY"
+    );
+    assert_eq!(fmt(1, &[192, 0, 2]), "\\# 3 C00002");
+    assert_eq!(fmt(4321, &[]), "\\# 0");
+    assert_eq!(timestamp(1_000_000_000), "20010909014640");
+    assert_eq!(base64(b"hello"), "aGVsbG8=");
+    assert_eq!(base32hex(b"\xff\x00"), "VS00");
+}
+
+#[test]
+fn test_display() {
+    use super::Context;
+    let ctx = Context::new().unwrap();
+    let answer = ctx.resolve("localhost", 1, 1).unwrap();
+    let s = answer.to_string();
+    assert!(s.starts_with(";; ->>HEADER<<- opcode: QUERY, status: NOERROR"));
+    assert!(s.contains(";; DNSSEC: insecure\n"));
+    assert!(s.contains("\n;localhost.\t\tIN\tA\n"));
+    assert!(s.contains("\nlocalhost.\t"));
+    assert!(s.ends_with("\tIN\tA\t127.0.0.1\n"));
+}
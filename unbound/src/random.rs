@@ -0,0 +1,21 @@
+//! Randomness for tie-breaking and weighted selection. Not suitable for
+//! cryptographic use.
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns a random number below `n`, which must not be zero.
+pub(crate) fn below(n: u64) -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_usize(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish() % n
+}
+
+/// Shuffle `items` in place.
+pub(crate) fn shuffle<T>(items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, below(i as u64 + 1) as usize);
+    }
+}
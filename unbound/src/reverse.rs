@@ -0,0 +1,100 @@
+//! Reverse lookups.
+use std::fmt::Write;
+use std::net::IpAddr;
+
+use super::ip::answer_addrs;
+use super::wire;
+use super::{Answer, Context, Result, Security};
+
+/// Returns the `in-addr.arpa.` or `ip6.arpa.` name of an address.
+pub fn reverse_name(addr: &IpAddr) -> String {
+    let mut name = String::with_capacity(73);
+    match *addr {
+        IpAddr::V4(addr) => {
+            for octet in addr.octets().iter().rev() {
+                write!(name, "{}.", octet).unwrap();
+            }
+            name.push_str("in-addr.arpa.");
+        }
+        IpAddr::V6(addr) => {
+            for octet in addr.octets().iter().rev() {
+                write!(name, "{:x}.{:x}.", octet & 0xF, octet >> 4).unwrap();
+            }
+            name.push_str("ip6.arpa.");
+        }
+    }
+    name
+}
+
+/// The names in a PTR answer, as returned by
+/// [Context::lookup_ptr](struct.Context.html#method.lookup_ptr) and the DNS-SD
+/// browsing methods.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupPtr {
+    names: Vec<String>,
+    security: Security,
+}
+
+impl LookupPtr {
+    pub(crate) fn from_answer(answer: &Answer) -> LookupPtr {
+        LookupPtr {
+            names: answer
+                .data()
+                .filter_map(|data| wire::read_rdata_name(data, 0))
+                .map(|(name, _)| name)
+                .collect(),
+            security: answer.security(),
+        }
+    }
+    /// Returns the names found in presentation format with a trailing dot.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+    /// Returns the outcome of DNSSEC validation of the PTR query.
+    pub fn security(&self) -> &Security {
+        &self.security
+    }
+}
+
+impl Context {
+    /// Look up the names of an address.
+    pub fn lookup_ptr(&self, addr: IpAddr) -> Result<LookupPtr> {
+        let answer = self.resolve(&reverse_name(&addr), 12, 1)?;
+        Ok(LookupPtr::from_answer(&answer))
+    }
+    /// Perform forward-confirmed reverse DNS: look up the names of an address,
+    /// then the addresses of those names, returning the names that resolve back
+    /// to the original address. The forward lookups are made concurrently.
+    pub fn check_fcrdns(&self, addr: IpAddr) -> Result<Vec<String>> {
+        let names = self.lookup_ptr(addr)?.names;
+        let rrtype = if addr.is_ipv4() { 1 } else { 28 };
+        let queries: Vec<_> = names.iter().map(|n| (&n[..], rrtype, 1)).collect();
+        let results = self.resolve_all(&queries)?;
+        Ok(names
+            .iter()
+            .zip(results)
+            .filter(|(_, result)| match result {
+                Ok(answer) => answer_addrs(answer).contains(&addr),
+                Err(_) => false,
+            })
+            .map(|(name, _)| name.clone())
+            .collect())
+    }
+}
+
+#[test]
+fn test_reverse_name() {
+    let v4: IpAddr = "192.0.2.1".parse().unwrap();
+    assert_eq!(reverse_name(&v4), "1.2.0.192.in-addr.arpa.");
+    let v6: IpAddr = "2001:db8::567:89ab".parse().unwrap();
+    assert_eq!(
+        reverse_name(&v6),
+        "b.a.9.8.7.6.5.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa."
+    );
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    let localhost: IpAddr = "127.0.0.1".parse().unwrap();
+    let lookup = ctx.lookup_ptr(localhost).unwrap();
+    assert_eq!(lookup.names(), ["localhost."]);
+    assert_eq!(ctx.check_fcrdns(localhost).unwrap(), ["localhost."]);
+}
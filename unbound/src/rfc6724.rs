@@ -0,0 +1,163 @@
+//! Destination address selection ([RFC 6724](https://tools.ietf.org/html/rfc6724)).
+use std::cmp::Ordering;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, UdpSocket};
+
+// The default policy table: prefix, prefix length, precedence, label.
+const POLICY: &[([u16; 8], u32, u8, u8)] = &[
+    ([0, 0, 0, 0, 0, 0, 0, 1], 128, 50, 0),
+    ([0, 0, 0, 0, 0, 0xffff, 0, 0], 96, 35, 4),
+    ([0, 0, 0, 0, 0, 0, 0, 0], 96, 1, 3),
+    ([0x2001, 0, 0, 0, 0, 0, 0, 0], 32, 5, 5),
+    ([0x2002, 0, 0, 0, 0, 0, 0, 0], 16, 30, 2),
+    ([0x3ffe, 0, 0, 0, 0, 0, 0, 0], 16, 1, 12),
+    ([0xfec0, 0, 0, 0, 0, 0, 0, 0], 10, 1, 11),
+    ([0xfc00, 0, 0, 0, 0, 0, 0, 0], 7, 3, 13),
+    ([0, 0, 0, 0, 0, 0, 0, 0], 0, 40, 1),
+];
+
+const SCOPE_LINK_LOCAL: u8 = 2;
+const SCOPE_SITE_LOCAL: u8 = 5;
+const SCOPE_GLOBAL: u8 = 14;
+
+fn to_v6(addr: &IpAddr) -> Ipv6Addr {
+    match *addr {
+        IpAddr::V4(addr) => addr.to_ipv6_mapped(),
+        IpAddr::V6(addr) => addr,
+    }
+}
+
+fn common_prefix_len(a: &Ipv6Addr, b: &Ipv6Addr) -> u32 {
+    let (a, b) = (u128::from(*a), u128::from(*b));
+    (a ^ b).leading_zeros()
+}
+
+// Returns the precedence and label of an address.
+fn policy(addr: &IpAddr) -> (u8, u8) {
+    let addr = to_v6(addr);
+    POLICY
+        .iter()
+        .find(|&&(prefix, len, _, _)| common_prefix_len(&addr, &Ipv6Addr::from(prefix)) >= len)
+        .map(|&(_, _, precedence, label)| (precedence, label))
+        .unwrap_or((40, 1))
+}
+
+fn scope(addr: &IpAddr) -> u8 {
+    match *addr {
+        IpAddr::V4(addr) => {
+            if addr.is_loopback() || addr.is_link_local() {
+                SCOPE_LINK_LOCAL
+            } else {
+                SCOPE_GLOBAL
+            }
+        }
+        IpAddr::V6(addr) => {
+            let segments = addr.segments();
+            if addr.is_multicast() {
+                (segments[0] & 0xf) as u8
+            } else if addr.is_loopback() || segments[0] & 0xffc0 == 0xfe80 {
+                SCOPE_LINK_LOCAL
+            } else if segments[0] & 0xffc0 == 0xfec0 {
+                SCOPE_SITE_LOCAL
+            } else {
+                SCOPE_GLOBAL
+            }
+        }
+    }
+}
+
+/// Returns the local address the host would use to reach a destination.
+/// No packets are sent.
+fn source_addr(dst: &IpAddr) -> Option<IpAddr> {
+    let bind: SocketAddr = match *dst {
+        IpAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        IpAddr::V6(_) => ([0u16; 8], 0).into(),
+    };
+    let socket = UdpSocket::bind(bind).ok()?;
+    socket.connect((*dst, 9)).ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip())
+}
+
+// Rules 1, 2, 5, 6, 8 and 9 of section 6. Rules 3, 4 and 7 depend on
+// information not available here.
+fn compare(a: &(IpAddr, Option<IpAddr>), b: &(IpAddr, Option<IpAddr>)) -> Ordering {
+    let (da, sa) = match *a {
+        (da, Some(sa)) => (da, sa),
+        (_, None) => {
+            return if b.1.is_none() {
+                Ordering::Equal
+            } else {
+                Ordering::Greater
+            }
+        }
+    };
+    let (db, sb) = match *b {
+        (db, Some(sb)) => (db, sb),
+        (_, None) => return Ordering::Less,
+    };
+    let matches_scope = |d: &IpAddr, s: &IpAddr| scope(d) == scope(s);
+    let ord = matches_scope(&db, &sb).cmp(&matches_scope(&da, &sa));
+    if ord != Ordering::Equal {
+        return ord;
+    }
+    let matches_label = |d: &IpAddr, s: &IpAddr| policy(d).1 == policy(s).1;
+    let ord = matches_label(&db, &sb).cmp(&matches_label(&da, &sa));
+    if ord != Ordering::Equal {
+        return ord;
+    }
+    let ord = policy(&db).0.cmp(&policy(&da).0);
+    if ord != Ordering::Equal {
+        return ord;
+    }
+    let ord = scope(&da).cmp(&scope(&db));
+    if ord != Ordering::Equal {
+        return ord;
+    }
+    if let (IpAddr::V6(da), IpAddr::V6(sa), IpAddr::V6(db), IpAddr::V6(sb)) = (da, sa, db, sb) {
+        return common_prefix_len(&db, &sb).cmp(&common_prefix_len(&da, &sa));
+    }
+    Ordering::Equal
+}
+
+/// Sort destination addresses in order of preference using the given source
+/// address selection.
+pub(crate) fn sort_by_source<F>(addrs: &mut [SocketAddr], mut source: F)
+where
+    F: FnMut(&IpAddr) -> Option<IpAddr>,
+{
+    let mut keyed: Vec<_> = addrs
+        .iter()
+        .map(|addr| ((addr.ip(), source(&addr.ip())), *addr))
+        .collect();
+    keyed.sort_by(|a, b| compare(&a.0, &b.0));
+    for (dst, (_, addr)) in addrs.iter_mut().zip(keyed) {
+        *dst = addr;
+    }
+}
+
+/// Sort destination addresses in order of preference using the host's
+/// local addresses.
+pub(crate) fn sort(addrs: &mut [SocketAddr]) {
+    sort_by_source(addrs, source_addr)
+}
+
+#[test]
+fn test_sort() {
+    let addr = |s: &str| SocketAddr::new(s.parse().unwrap(), 443);
+    // A host with global IPv4 and only link-local IPv6 prefers IPv4.
+    let mut addrs = [addr("2001:db8::1"), addr("198.51.100.1")];
+    sort_by_source(&mut addrs, |dst| match *dst {
+        IpAddr::V4(_) => Some("192.0.2.10".parse().unwrap()),
+        IpAddr::V6(_) => Some("fe80::10".parse().unwrap()),
+    });
+    assert_eq!(addrs, [addr("198.51.100.1"), addr("2001:db8::1")]);
+    // With global IPv6 it's preferred; unreachable destinations go last.
+    let mut addrs = [addr("10.0.0.1"), addr("2001:db8::1"), addr("2001:db8:1::1")];
+    sort_by_source(&mut addrs, |dst| match *dst {
+        IpAddr::V4(_) => None,
+        IpAddr::V6(_) => Some("2001:db8:1::10".parse().unwrap()),
+    });
+    assert_eq!(
+        addrs,
+        [addr("2001:db8:1::1"), addr("2001:db8::1"), addr("10.0.0.1")]
+    );
+}
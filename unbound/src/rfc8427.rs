@@ -0,0 +1,231 @@
+//! Serialization of answers as [RFC 8427](https://tools.ietf.org/html/rfc8427) JSON
+//! objects.
+//!
+//! The decoded answer packet is represented with the members RFC 8427 defines for
+//! DNS messages. The remaining fields of `ub_result` are represented with
+//! additional members: `canonname`, `havedata`, `nxdomain`, `secure`, `bogus`,
+//! `whyBogus`, `ttl` and `dataHEX`.
+//!
+//! Deserialization reads `QNAME`, `QTYPE`, `QCLASS`, `RCODE`, `messageOctetsHEX`
+//! and the additional members to produce an
+//! [OwnedAnswer](../struct.OwnedAnswer.html). Other members are ignored as they
+//! are derived from the packet.
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::owned::Fields;
+use super::wire::{self, Message, Record};
+use super::{Answer, OwnedAnswer};
+
+#[derive(Serialize, Deserialize)]
+struct Document {
+    #[serde(rename = "ID", default, skip_serializing_if = "Option::is_none")]
+    id: Option<u16>,
+    #[serde(rename = "QR", default, skip_serializing_if = "Option::is_none")]
+    qr: Option<bool>,
+    #[serde(rename = "Opcode", default, skip_serializing_if = "Option::is_none")]
+    opcode: Option<u8>,
+    #[serde(rename = "AA", default, skip_serializing_if = "Option::is_none")]
+    aa: Option<bool>,
+    #[serde(rename = "TC", default, skip_serializing_if = "Option::is_none")]
+    tc: Option<bool>,
+    #[serde(rename = "RD", default, skip_serializing_if = "Option::is_none")]
+    rd: Option<bool>,
+    #[serde(rename = "RA", default, skip_serializing_if = "Option::is_none")]
+    ra: Option<bool>,
+    #[serde(rename = "AD", default, skip_serializing_if = "Option::is_none")]
+    ad: Option<bool>,
+    #[serde(rename = "CD", default, skip_serializing_if = "Option::is_none")]
+    cd: Option<bool>,
+    #[serde(rename = "RCODE")]
+    rcode: u16,
+    #[serde(rename = "QDCOUNT", default, skip_serializing_if = "Option::is_none")]
+    qdcount: Option<u16>,
+    #[serde(rename = "ANCOUNT", default, skip_serializing_if = "Option::is_none")]
+    ancount: Option<u16>,
+    #[serde(rename = "NSCOUNT", default, skip_serializing_if = "Option::is_none")]
+    nscount: Option<u16>,
+    #[serde(rename = "ARCOUNT", default, skip_serializing_if = "Option::is_none")]
+    arcount: Option<u16>,
+    #[serde(rename = "QNAME")]
+    qname: String,
+    #[serde(rename = "QTYPE")]
+    qtype: u16,
+    #[serde(rename = "QCLASS")]
+    qclass: u16,
+    #[serde(rename = "answerRRs", default, skip_serializing_if = "Option::is_none")]
+    answer_rrs: Option<Vec<Rr>>,
+    #[serde(
+        rename = "authorityRRs",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    authority_rrs: Option<Vec<Rr>>,
+    #[serde(
+        rename = "additionalRRs",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    additional_rrs: Option<Vec<Rr>>,
+    #[serde(
+        rename = "messageOctetsHEX",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    message_octets_hex: Option<String>,
+    canonname: Option<String>,
+    havedata: bool,
+    nxdomain: bool,
+    secure: bool,
+    bogus: bool,
+    #[serde(rename = "whyBogus")]
+    why_bogus: Option<String>,
+    ttl: u32,
+    #[serde(rename = "dataHEX")]
+    data_hex: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Rr {
+    #[serde(rename = "NAME")]
+    name: String,
+    #[serde(rename = "TYPE")]
+    rrtype: u16,
+    #[serde(rename = "CLASS")]
+    class: u16,
+    #[serde(rename = "TTL")]
+    ttl: u32,
+    #[serde(rename = "RDLENGTH")]
+    rdlength: u16,
+    #[serde(rename = "RDATAHEX")]
+    rdatahex: String,
+}
+
+impl<'a, 'b> From<&'b Record<'a>> for Rr {
+    fn from(record: &'b Record<'a>) -> Rr {
+        Rr {
+            name: record.name.clone(),
+            rrtype: record.rrtype,
+            class: record.class,
+            ttl: record.ttl,
+            rdlength: record.rdata().len() as u16,
+            rdatahex: wire::hex(record.rdata()),
+        }
+    }
+}
+
+impl<'a> From<&'a OwnedAnswer> for Document {
+    fn from(answer: &'a OwnedAnswer) -> Document {
+        let mut doc = Document {
+            id: None,
+            qr: None,
+            opcode: None,
+            aa: None,
+            tc: None,
+            rd: None,
+            ra: None,
+            ad: None,
+            cd: None,
+            rcode: answer.rcode(),
+            qdcount: None,
+            ancount: None,
+            nscount: None,
+            arcount: None,
+            qname: answer.qname().to_owned(),
+            qtype: answer.qtype(),
+            qclass: answer.qclass(),
+            answer_rrs: None,
+            authority_rrs: None,
+            additional_rrs: None,
+            message_octets_hex: answer.answer().map(wire::hex),
+            canonname: answer.canonname().map(str::to_owned),
+            havedata: answer.havedata(),
+            nxdomain: answer.nxdomain(),
+            secure: answer.secure(),
+            bogus: answer.bogus(),
+            why_bogus: answer.why_bogus().map(str::to_owned),
+            ttl: answer.ttl(),
+            data_hex: answer.data().map(wire::hex).collect(),
+        };
+        if let Some(msg) = answer.answer().and_then(Message::parse) {
+            let h = msg.header;
+            doc.id = Some(h.id);
+            doc.qr = Some(h.qr);
+            doc.opcode = Some(h.opcode);
+            doc.aa = Some(h.aa);
+            doc.tc = Some(h.tc);
+            doc.rd = Some(h.rd);
+            doc.ra = Some(h.ra);
+            doc.ad = Some(h.ad);
+            doc.cd = Some(h.cd);
+            doc.qdcount = Some(h.qdcount);
+            doc.ancount = Some(h.ancount);
+            doc.nscount = Some(h.nscount);
+            doc.arcount = Some(h.arcount);
+            doc.answer_rrs = Some(msg.answer.iter().map(Rr::from).collect());
+            doc.authority_rrs = Some(msg.authority.iter().map(Rr::from).collect());
+            doc.additional_rrs = Some(msg.additional.iter().map(Rr::from).collect());
+        }
+        doc
+    }
+}
+
+impl Serialize for OwnedAnswer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Document::from(self).serialize(serializer)
+    }
+}
+
+impl Serialize for Answer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Document::from(&OwnedAnswer::from(self)).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for OwnedAnswer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<OwnedAnswer, D::Error> {
+        let doc = Document::deserialize(deserializer)?;
+        let unhex = |s: &str| wire::unhex(s).ok_or_else(|| D::Error::custom("invalid hex"));
+        let answer = match doc.message_octets_hex {
+            Some(ref s) => Some(unhex(s)?),
+            None => None,
+        };
+        let data = doc
+            .data_hex
+            .iter()
+            .map(|s| unhex(s))
+            .collect::<Result<_, _>>()?;
+        Ok(OwnedAnswer::from_fields(Fields {
+            qname: doc.qname,
+            qtype: doc.qtype,
+            qclass: doc.qclass,
+            data,
+            canonname: doc.canonname,
+            rcode: doc.rcode,
+            answer,
+            havedata: doc.havedata,
+            nxdomain: doc.nxdomain,
+            secure: doc.secure,
+            bogus: doc.bogus,
+            why_bogus: doc.why_bogus,
+            ttl: doc.ttl,
+        }))
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    use super::Context;
+    extern crate serde_json;
+    let ctx = Context::new().unwrap();
+    let answer = ctx.resolve("localhost", 1, 1).unwrap();
+    let json = serde_json::to_value(&answer).unwrap();
+    assert_eq!(json["QNAME"], "localhost");
+    assert_eq!(json["QTYPE"], 1);
+    assert_eq!(json["QR"], true);
+    assert_eq!(json["answerRRs"][0]["NAME"], "localhost.");
+    assert_eq!(json["answerRRs"][0]["RDATAHEX"], "7F000001");
+    assert_eq!(json["dataHEX"][0], "7F000001");
+    let owned: OwnedAnswer = serde_json::from_value(json).unwrap();
+    assert_eq!(owned, OwnedAnswer::from(&answer));
+}
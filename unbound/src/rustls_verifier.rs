@@ -0,0 +1,282 @@
+//! A rustls server certificate verifier backed by DANE.
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
+
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
+use openssl::x509::X509;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{self, WebPkiSupportedAlgorithms};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{CertificateError, DigitallySignedStruct, SignatureScheme};
+
+use super::dane::{dane_ta_anchors, verify_tlsa, USAGE_DANE_EE, USAGE_DANE_TA};
+use super::{Context, Tlsa};
+
+/// When [DaneVerifier](struct.DaneVerifier.html) defers to its fallback
+/// verifier.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Fallback {
+    /// When the server has no usable TLSA records: the TLSA answer was a secure
+    /// denial, insecure, or contained no DANE-TA or DANE-EE records.
+    NoTlsa,
+    /// As with `NoTlsa`, and also when the TLSA lookup failed or was bogus.
+    Unavailable,
+}
+
+/// A rustls `ServerCertVerifier` that authenticates servers with TLSA records
+/// looked up through a [Context](struct.Context.html).
+///
+/// Only TLSA records from DNSSEC secure answers are used. DANE-EE records are
+/// matched against the end-entity certificate without checking its names or
+/// validity period. DANE-TA records must match a certificate the server
+/// presented that issued the chain, the certificates up to it must be within
+/// their validity periods, and the end-entity certificate must be valid for the
+/// server name. PKIX-TA and PKIX-EE records are ignored.
+///
+/// Without a fallback verifier, servers without usable TLSA records are
+/// rejected.
+pub struct DaneVerifier {
+    ctx: Arc<Context>,
+    port: u16,
+    fallback: Option<(Fallback, Arc<dyn ServerCertVerifier>)>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl DaneVerifier {
+    /// Create a verifier for servers on `port`, whose TLSA records are found at
+    /// `_port._tcp.name`.
+    pub fn new(ctx: Arc<Context>, port: u16) -> DaneVerifier {
+        DaneVerifier {
+            ctx,
+            port,
+            fallback: None,
+            algorithms: crypto::ring::default_provider().signature_verification_algorithms,
+        }
+    }
+    /// Defer to `verifier`, typically a `WebPkiServerVerifier`, as directed by
+    /// `policy`.
+    pub fn fallback(
+        mut self,
+        policy: Fallback,
+        verifier: Arc<dyn ServerCertVerifier>,
+    ) -> DaneVerifier {
+        self.fallback = Some((policy, verifier));
+        self
+    }
+    // Returns the fallback verifier if the policy allows its use.
+    fn fallback_for(&self, lookup_failed: bool) -> Option<&Arc<dyn ServerCertVerifier>> {
+        match self.fallback {
+            Some((Fallback::Unavailable, ref verifier)) => Some(verifier),
+            Some((Fallback::NoTlsa, ref verifier)) if !lookup_failed => Some(verifier),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for DaneVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DaneVerifier")
+            .field("port", &self.port)
+            .field("fallback", &self.fallback.as_ref().map(|f| f.0))
+            .finish()
+    }
+}
+
+fn bad_encoding(_: openssl::error::ErrorStack) -> rustls::Error {
+    rustls::Error::InvalidCertificate(CertificateError::BadEncoding)
+}
+
+// Checks that each certificate is within its validity period at `now`.
+fn check_validity(chain: &[X509], now: &Asn1TimeRef) -> Result<(), CertificateError> {
+    for cert in chain {
+        if cert.not_before() > now {
+            return Err(CertificateError::NotValidYet);
+        }
+        if cert.not_after() < now {
+            return Err(CertificateError::Expired);
+        }
+    }
+    Ok(())
+}
+
+impl ServerCertVerifier for DaneVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let lookup = match *server_name {
+            ServerName::DnsName(ref name) => self.ctx.lookup_tlsa(self.port, "tcp", name.as_ref()),
+            _ => Ok(Vec::new()),
+        };
+        let records: Vec<Tlsa> = match lookup {
+            Ok(records) => records
+                .into_iter()
+                .filter(|r| r.usage() == USAGE_DANE_TA || r.usage() == USAGE_DANE_EE)
+                .collect(),
+            Err(err) => {
+                return match self.fallback_for(true) {
+                    Some(verifier) => verifier.verify_server_cert(
+                        end_entity,
+                        intermediates,
+                        server_name,
+                        ocsp_response,
+                        now,
+                    ),
+                    None => Err(rustls::Error::General(format!(
+                        "TLSA lookup failed: {}",
+                        err
+                    ))),
+                };
+            }
+        };
+        if records.is_empty() {
+            return match self.fallback_for(false) {
+                Some(verifier) => verifier.verify_server_cert(
+                    end_entity,
+                    intermediates,
+                    server_name,
+                    ocsp_response,
+                    now,
+                ),
+                None => Err(rustls::Error::General("no usable TLSA records".to_owned())),
+            };
+        }
+        let chain = Some(end_entity)
+            .into_iter()
+            .chain(intermediates)
+            .map(|der| X509::from_der(der).map_err(bad_encoding))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ee_records: Vec<_> = records
+            .iter()
+            .filter(|r| r.usage() == USAGE_DANE_EE)
+            .cloned()
+            .collect();
+        if verify_tlsa(&ee_records, &chain, false) {
+            return Ok(ServerCertVerified::assertion());
+        }
+        // The certificates from the end-entity certificate up to a trust anchor
+        // matching a DANE-TA record must be valid now.
+        let now = Asn1Time::from_unix(now.as_secs() as i64)
+            .map_err(|err| rustls::Error::General(err.to_string()))?;
+        let mut valid = Err(CertificateError::ApplicationVerificationFailure);
+        for end in dane_ta_anchors(&records, &chain) {
+            valid = check_validity(&chain[..=end], &now);
+            if valid.is_ok() {
+                break;
+            }
+        }
+        valid.map_err(rustls::Error::InvalidCertificate)?;
+        // A DANE-TA record matched, so the end-entity certificate's names must
+        // be checked.
+        webpki::EndEntityCert::try_from(end_entity)
+            .and_then(|cert| cert.verify_is_valid_for_subject_name(server_name))
+            .map(|_| ServerCertVerified::assertion())
+            .map_err(|_| rustls::Error::InvalidCertificate(CertificateError::NotValidForName))
+    }
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+#[test]
+fn test_dane_verifier() {
+    use super::dane::fixture_chain;
+    use super::signed_zone::SignedZone;
+    use openssl::hash::{hash, MessageDigest};
+    use rustls::client::WebPkiServerVerifier;
+    use rustls::RootCertStore;
+    use std::time::Duration;
+    let (ee, ca, _) = fixture_chain("www.dane.example");
+    let spki = ee.public_key().unwrap().public_key_to_der().unwrap();
+    let digest = hash(MessageDigest::sha256(), &spki).unwrap();
+    let dane_ee = [&[3, 1, 1][..], &digest].concat();
+    let dane_ta = [&[2, 0, 0][..], &ca.to_der().unwrap()].concat();
+    let mut zone = SignedZone::new("dane.example.");
+    zone.add("_443._tcp.www.dane.example.", 52, dane_ee);
+    zone.add("_8443._tcp.www.dane.example.", 52, dane_ta.clone());
+    zone.add("_8443._tcp.ta.dane.example.", 52, dane_ta);
+    // A name without TLSA records, whose absence is proven securely.
+    zone.add("_9444._tcp.www.dane.example.", 16, vec![0]);
+    let ctx = Arc::new(zone.context());
+    let ee_der = CertificateDer::from(ee.to_der().unwrap());
+    let ca_der = CertificateDer::from(ca.to_der().unwrap());
+    let chain = [ca_der.clone()];
+    let (other_ee, _, _) = fixture_chain("www.dane.example");
+    let other_der = CertificateDer::from(other_ee.to_der().unwrap());
+    let www = ServerName::try_from("www.dane.example").unwrap();
+    let ta = ServerName::try_from("ta.dane.example").unwrap();
+    let now = UnixTime::now();
+    let verify = |verifier: &DaneVerifier, ee: &CertificateDer, chain: &[CertificateDer], name| {
+        verifier
+            .verify_server_cert(ee, chain, name, &[], now)
+            .is_ok()
+    };
+    // DANE-EE matches the end-entity certificate alone.
+    let verifier = DaneVerifier::new(ctx.clone(), 443);
+    assert!(verify(&verifier, &ee_der, &[], &www));
+    assert!(!verify(&verifier, &other_der, &[], &www));
+    // DANE-TA requires the chain and a matching name.
+    let verifier = DaneVerifier::new(ctx.clone(), 8443);
+    assert!(verify(&verifier, &ee_der, &chain, &www));
+    assert!(!verify(&verifier, &ee_der, &[], &www));
+    assert!(!verify(&verifier, &ee_der, &chain, &ta));
+    // Once the end-entity certificate expires DANE-TA fails, while DANE-EE
+    // ignores validity periods.
+    let later = UnixTime::since_unix_epoch(Duration::from_secs(now.as_secs() + 2 * 86400));
+    assert_eq!(
+        verifier
+            .verify_server_cert(&ee_der, &chain, &www, &[], later)
+            .err(),
+        Some(rustls::Error::InvalidCertificate(CertificateError::Expired))
+    );
+    let verifier = DaneVerifier::new(ctx.clone(), 443);
+    assert!(verifier
+        .verify_server_cert(&ee_der, &[], &www, &[], later)
+        .is_ok());
+    // Without usable TLSA records the fallback policy decides. The stand-in
+    // zone can't prove that names don't exist, so a lookup on port 9443 is
+    // bogus, while port 9444 is a secure denial.
+    let mut roots = RootCertStore::empty();
+    roots.add(ca_der).unwrap();
+    let webpki = WebPkiServerVerifier::builder_with_provider(
+        Arc::new(roots),
+        Arc::new(crypto::ring::default_provider()),
+    )
+    .build()
+    .unwrap();
+    let verifier = DaneVerifier::new(ctx.clone(), 9443);
+    assert!(!verify(&verifier, &ee_der, &[], &www));
+    let verifier = DaneVerifier::new(ctx.clone(), 9443).fallback(Fallback::NoTlsa, webpki.clone());
+    assert!(!verify(&verifier, &ee_der, &[], &www));
+    let verifier =
+        DaneVerifier::new(ctx.clone(), 9443).fallback(Fallback::Unavailable, webpki.clone());
+    assert!(verify(&verifier, &ee_der, &[], &www));
+    let verifier = DaneVerifier::new(ctx.clone(), 9444);
+    assert!(!verify(&verifier, &ee_der, &[], &www));
+    let verifier = DaneVerifier::new(ctx.clone(), 9444).fallback(Fallback::NoTlsa, webpki.clone());
+    assert!(verify(&verifier, &ee_der, &[], &www));
+    // A TLSA mismatch isn't rescued by the fallback.
+    let verifier = DaneVerifier::new(ctx, 443).fallback(Fallback::Unavailable, webpki);
+    assert!(!verify(&verifier, &other_der, &[], &www));
+}
@@ -0,0 +1,204 @@
+//! DNSSEC validation status.
+use std::fmt;
+
+/// The outcome of DNSSEC validation of an answer.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Security {
+    /// The answer validated from a trust anchor.
+    Secure,
+    /// The answer is provably unsigned or no trust anchor covers it.
+    Insecure,
+    /// Validation failed.
+    Bogus(BogusReason),
+    /// Validation could not be completed, as when the query failed with SERVFAIL
+    /// without libunbound reporting a validation failure.
+    Indeterminate,
+}
+
+impl Security {
+    pub(crate) fn from_fields(
+        secure: bool,
+        bogus: bool,
+        why_bogus: Option<&str>,
+        rcode: u16,
+    ) -> Security {
+        if secure {
+            Security::Secure
+        } else if bogus {
+            Security::Bogus(BogusReason::parse(why_bogus.unwrap_or("")))
+        } else if rcode == 2 {
+            Security::Indeterminate
+        } else {
+            Security::Insecure
+        }
+    }
+    /// Returns true if the answer is secure.
+    pub fn is_secure(&self) -> bool {
+        *self == Security::Secure
+    }
+    /// Replaces `self` with `other` if `other` is less secure, to combine the
+    /// outcomes of several queries. From least to most secure the outcomes are
+    /// `Bogus`, `Indeterminate`, `Insecure` and `Secure`; of two bogus outcomes
+    /// the first is kept.
+    pub fn downgrade(&mut self, other: Security) {
+        if other.rank() < self.rank() {
+            *self = other;
+        }
+    }
+    // Orders outcomes from least to most secure.
+    fn rank(&self) -> u8 {
+        match *self {
+            Security::Bogus(_) => 0,
+            Security::Indeterminate => 1,
+            Security::Insecure => 2,
+            Security::Secure => 3,
+        }
+    }
+    /// Returns the reason validation failed, if it did.
+    pub fn bogus_reason(&self) -> Option<&BogusReason> {
+        match *self {
+            Security::Bogus(ref reason) => Some(reason),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Security {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Security::Secure => f.write_str("secure"),
+            Security::Insecure => f.write_str("insecure"),
+            Security::Bogus(ref reason) => write!(f, "bogus: {}", reason.text()),
+            Security::Indeterminate => f.write_str("indeterminate"),
+        }
+    }
+}
+
+/// The category of a DNSSEC validation failure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BogusCategory {
+    /// A signature's expiration time has passed.
+    SignatureExpired,
+    /// A signature's inception time has not yet arrived.
+    SignatureNotYetValid,
+    /// A signature did not verify.
+    SignatureInvalid,
+    /// Expected signatures were missing.
+    MissingSignature,
+    /// A DNSKEY RRset needed for validation could not be obtained.
+    MissingDnskey,
+    /// No DNSKEY matched the DS records of the zone.
+    DsMismatch,
+    /// An NSEC or NSEC3 proof of non-existence or insecure delegation failed.
+    NsecProofFailure,
+    /// Records needed for validation could not be fetched from the zone's servers.
+    LookupFailure,
+    /// A failure not falling into another category.
+    Other,
+}
+
+/// Why an answer is bogus, derived from libunbound's `why_bogus` text.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BogusReason {
+    category: BogusCategory,
+    zone: Option<String>,
+    text: String,
+}
+
+// Matched in order against the lower cased text.
+const CATEGORIES: &[(&str, BogusCategory)] = &[
+    ("signature expired", BogusCategory::SignatureExpired),
+    ("before inception", BogusCategory::SignatureNotYetValid),
+    ("crypto failed", BogusCategory::SignatureInvalid),
+    ("ds hash mismatch", BogusCategory::DsMismatch),
+    ("no keys have a ds", BogusCategory::DsMismatch),
+    ("did not match ds", BogusCategory::DsMismatch),
+    ("no dnskey", BogusCategory::MissingDnskey),
+    ("could not fetch dnskey", BogusCategory::MissingDnskey),
+    ("failed to prime trust anchor", BogusCategory::MissingDnskey),
+    ("nsec", BogusCategory::NsecProofFailure),
+    ("proof", BogusCategory::NsecProofFailure),
+    ("no signatures", BogusCategory::MissingSignature),
+    ("no rrsig", BogusCategory::MissingSignature),
+    ("no dnssec records", BogusCategory::MissingSignature),
+    ("unsigned", BogusCategory::MissingSignature),
+    ("timeout", BogusCategory::LookupFailure),
+    ("servfail", BogusCategory::LookupFailure),
+    ("exceeded", BogusCategory::LookupFailure),
+    ("could not fetch", BogusCategory::LookupFailure),
+];
+
+// Phrases in libunbound's text that precede the name of the failing zone.
+const ZONE_PREFIXES: &[&str] = &["for key ", "for trust anchor ", "key for validation "];
+
+impl BogusReason {
+    /// Parse libunbound's `why_bogus` text.
+    pub fn parse(text: &str) -> BogusReason {
+        let lower = text.to_ascii_lowercase();
+        let category = CATEGORIES
+            .iter()
+            .find(|&&(phrase, _)| lower.contains(phrase))
+            .map(|&(_, category)| category)
+            .unwrap_or(BogusCategory::Other);
+        let zone = ZONE_PREFIXES.iter().find_map(|prefix| {
+            let start = lower.find(prefix)? + prefix.len();
+            let zone = text[start..].split_whitespace().next()?;
+            Some(zone.trim_end_matches(',').to_owned())
+        });
+        BogusReason {
+            category,
+            zone,
+            text: text.to_owned(),
+        }
+    }
+    /// Returns the category of failure.
+    pub fn category(&self) -> BogusCategory {
+        self.category
+    }
+    /// Returns the zone whose data failed to validate, if identified.
+    pub fn zone(&self) -> Option<&str> {
+        self.zone.as_deref()
+    }
+    /// Returns libunbound's text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[test]
+fn test_bogus_reason() {
+    let r = BogusReason::parse(
+        "validation failure <www.example.com. A IN>: signature expired from \
+         192.0.2.1 for key example.com. while building chain of trust",
+    );
+    assert_eq!(r.category(), BogusCategory::SignatureExpired);
+    assert_eq!(r.zone(), Some("example.com."));
+    let r = BogusReason::parse(
+        "validation failure <example.net. DNSKEY IN>: No DNSKEY record for key \
+         example.net. while building chain of trust",
+    );
+    assert_eq!(r.category(), BogusCategory::MissingDnskey);
+    assert_eq!(r.zone(), Some("example.net."));
+    let r = BogusReason::parse("validation failure <x. A IN>: nxdomain proof failed");
+    assert_eq!(r.category(), BogusCategory::NsecProofFailure);
+    assert_eq!(r.zone(), None);
+    assert_eq!(BogusReason::parse("?").category(), BogusCategory::Other);
+    assert_eq!(
+        Security::from_fields(false, false, None, 2),
+        Security::Indeterminate
+    );
+}
+
+#[test]
+fn test_downgrade() {
+    let bogus = Security::Bogus(BogusReason::parse("?"));
+    let mut security = Security::Secure;
+    security.downgrade(Security::Insecure);
+    assert_eq!(security, Security::Insecure);
+    security.downgrade(Security::Secure);
+    assert_eq!(security, Security::Insecure);
+    security.downgrade(Security::Indeterminate);
+    security.downgrade(bogus.clone());
+    security.downgrade(Security::Insecure);
+    assert_eq!(security, bogus);
+}
@@ -0,0 +1,242 @@
+//! A DNSSEC signed zone served over UDP on the loopback interface, used by tests
+//! that need secure answers without network access.
+use std::ffi::CString;
+use std::net::UdpSocket;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use openssl::base64;
+use openssl::bn::BigNumContext;
+use openssl::ec::{EcGroup, EcKey, PointConversionForm};
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::{hash, MessageDigest};
+use openssl::nid::Nid;
+use openssl::pkey::Private;
+
+use super::sys;
+use super::wire::Message;
+use super::Context;
+
+const TTL: u32 = 3600;
+
+fn name_to_wire(name: &str) -> Vec<u8> {
+    let mut wire = Vec::new();
+    for label in name
+        .trim_end_matches('.')
+        .split('.')
+        .filter(|l| !l.is_empty())
+    {
+        wire.push(label.len() as u8);
+        wire.extend(label.to_ascii_lowercase().bytes());
+    }
+    wire.push(0);
+    wire
+}
+
+// Orders names canonically (RFC 4034 section 6.1).
+fn canonical_key(name: &str) -> Vec<&str> {
+    name.trim_end_matches('.').rsplit('.').collect()
+}
+
+fn key_tag(rdata: &[u8]) -> u16 {
+    let mut ac: u32 = 0;
+    for (i, &b) in rdata.iter().enumerate() {
+        ac += if i & 1 == 0 {
+            u32::from(b) << 8
+        } else {
+            u32::from(b)
+        };
+    }
+    ac += (ac >> 16) & 0xFFFF;
+    (ac & 0xFFFF) as u16
+}
+
+/// A zone signed with a single ECDSA P-256 key.
+pub(crate) struct SignedZone {
+    origin: String,
+    key: EcKey<Private>,
+    dnskey: Vec<u8>,
+    records: Vec<(String, u16, Vec<u8>)>,
+}
+
+impl SignedZone {
+    pub(crate) fn new(origin: &str) -> SignedZone {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = EcKey::generate(&group).unwrap();
+        let mut bn = BigNumContext::new().unwrap();
+        let point = key
+            .public_key()
+            .to_bytes(&group, PointConversionForm::UNCOMPRESSED, &mut bn)
+            .unwrap();
+        let mut dnskey = vec![1, 1, 3, 13];
+        dnskey.extend_from_slice(&point[1..]);
+        let mut zone = SignedZone {
+            origin: origin.to_ascii_lowercase(),
+            key,
+            dnskey: dnskey.clone(),
+            records: Vec::new(),
+        };
+        zone.add(origin, 48, dnskey);
+        zone
+    }
+    pub(crate) fn add(&mut self, name: &str, rrtype: u16, rdata: Vec<u8>) {
+        let name = name.trim_end_matches('.').to_ascii_lowercase() + ".";
+        self.records.push((name, rrtype, rdata));
+    }
+    // Returns the RRSIG data covering an RRset.
+    fn sign(&self, name: &str, rrtype: u16, rdatas: &[&[u8]]) -> Vec<u8> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs() as u32;
+        let labels = name.split('.').filter(|l| !l.is_empty()).count() as u8;
+        let mut rrsig = rrtype.to_be_bytes().to_vec();
+        rrsig.extend_from_slice(&[13, labels]);
+        rrsig.extend_from_slice(&TTL.to_be_bytes());
+        rrsig.extend_from_slice(&(now + 86400).to_be_bytes());
+        rrsig.extend_from_slice(&(now - 3600).to_be_bytes());
+        rrsig.extend_from_slice(&key_tag(&self.dnskey).to_be_bytes());
+        rrsig.extend(name_to_wire(&self.origin));
+        let mut sorted = rdatas.to_vec();
+        sorted.sort();
+        let mut data = rrsig.clone();
+        for rdata in sorted {
+            data.extend(name_to_wire(name));
+            data.extend_from_slice(&rrtype.to_be_bytes());
+            data.extend_from_slice(&[0, 1]);
+            data.extend_from_slice(&TTL.to_be_bytes());
+            data.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            data.extend_from_slice(rdata);
+        }
+        let digest = hash(MessageDigest::sha256(), &data).unwrap();
+        let sig = EcdsaSig::sign(&digest, &self.key).unwrap();
+        rrsig.extend(sig.r().to_vec_padded(32).unwrap());
+        rrsig.extend(sig.s().to_vec_padded(32).unwrap());
+        rrsig
+    }
+    // Returns the NSEC data for a name in the zone, listing the types it has.
+    fn nsec(&self, name: &str) -> Vec<u8> {
+        let mut names: Vec<&str> = self.records.iter().map(|r| &r.0[..]).collect();
+        names.sort_by(|a, b| canonical_key(a).cmp(&canonical_key(b)));
+        names.dedup();
+        let i = names.iter().position(|&n| n == name).unwrap();
+        let mut nsec = name_to_wire(names.get(i + 1).unwrap_or(&names[0]));
+        let mut types: Vec<u16> = self
+            .records
+            .iter()
+            .filter(|r| r.0 == name)
+            .map(|r| r.1)
+            .chain(vec![46, 47])
+            .collect();
+        types.sort();
+        types.dedup();
+        for window in 0..=255 {
+            let bits: Vec<usize> = types
+                .iter()
+                .filter(|&&t| t >> 8 == window)
+                .map(|&t| usize::from(t & 0xFF))
+                .collect();
+            let len = match bits.last() {
+                Some(&last) => last / 8 + 1,
+                None => continue,
+            };
+            let mut bitmap = vec![0; len];
+            for bit in bits {
+                bitmap[bit / 8] |= 0x80 >> (bit % 8);
+            }
+            nsec.extend_from_slice(&[window as u8, len as u8]);
+            nsec.extend(bitmap);
+        }
+        nsec
+    }
+    // Answers a query. A name without records of the queried type gets a
+    // signed NSEC proof; names that don't exist get an unsigned NXDOMAIN.
+    fn respond(&self, query: &[u8]) -> Option<Vec<u8>> {
+        let msg = Message::parse(query)?;
+        let question = msg.question.first()?;
+        let qname = question.name.to_ascii_lowercase();
+        let rdatas: Vec<&[u8]> = self
+            .records
+            .iter()
+            .filter(|r| r.0 == qname && r.1 == question.qtype)
+            .map(|r| &r.2[..])
+            .collect();
+        let exists = self.records.iter().any(|r| r.0 == qname);
+        let rcode: u16 = if exists { 0 } else { 3 };
+        let mut out = query[..2].to_vec();
+        out.extend_from_slice(&(0x8580 | rcode).to_be_bytes());
+        let ancount = if rdatas.is_empty() {
+            0
+        } else {
+            rdatas.len() + 1
+        };
+        let nodata = exists && rdatas.is_empty();
+        let nscount = if nodata { 2 } else { 0 };
+        for count in &[1, ancount as u16, nscount, 1] {
+            out.extend_from_slice(&count.to_be_bytes());
+        }
+        out.extend(name_to_wire(&qname));
+        out.extend_from_slice(&question.qtype.to_be_bytes());
+        out.extend_from_slice(&[0, 1]);
+        let push_rr = |out: &mut Vec<u8>, rrtype: u16, rdata: &[u8]| {
+            out.extend(name_to_wire(&qname));
+            out.extend_from_slice(&rrtype.to_be_bytes());
+            out.extend_from_slice(&[0, 1]);
+            out.extend_from_slice(&TTL.to_be_bytes());
+            out.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            out.extend_from_slice(rdata);
+        };
+        for rdata in &rdatas {
+            push_rr(&mut out, question.qtype, rdata);
+        }
+        if !rdatas.is_empty() {
+            let rrsig = self.sign(&qname, question.qtype, &rdatas);
+            push_rr(&mut out, 46, &rrsig);
+        }
+        if nodata {
+            let nsec = self.nsec(&qname);
+            let rrsig = self.sign(&qname, 47, &[&nsec]);
+            push_rr(&mut out, 47, &nsec);
+            push_rr(&mut out, 46, &rrsig);
+        }
+        // OPT with the DO bit set.
+        out.extend_from_slice(&[0, 0, 41, 0x04, 0xD0, 0, 0, 0x80, 0, 0, 0]);
+        Some(out)
+    }
+    /// Serve the zone and return a context that forwards to it and trusts its key.
+    pub(crate) fn context(self) -> Context {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        let ta = format!(
+            "{} DNSKEY 257 3 13 {}",
+            self.origin,
+            base64::encode_block(&self.dnskey[4..])
+        );
+        thread::spawn(move || {
+            let mut buf = [0; 512];
+            while let Ok((len, peer)) = socket.recv_from(&mut buf) {
+                if let Some(response) = self.respond(&buf[..len]) {
+                    let _ = socket.send_to(&response, peer);
+                }
+            }
+        });
+        let ctx = Context::new().unwrap();
+        ctx.set_option("do-not-query-localhost:", "no").unwrap();
+        ctx.add_ta(&ta).unwrap();
+        let fwd = CString::new(format!("{}@{}", addr.ip(), addr.port())).unwrap();
+        assert_eq!(unsafe { sys::ub_ctx_set_fwd(ctx.ub_ctx, fwd.as_ptr()) }, 0);
+        ctx
+    }
+}
+
+#[test]
+fn test_signed_zone() {
+    let mut zone = SignedZone::new("signed.example.");
+    zone.add("www.signed.example.", 1, vec![192, 0, 2, 1]);
+    let ctx = zone.context();
+    let answer = ctx.resolve("www.signed.example", 1, 1).unwrap();
+    assert!(answer.secure(), "{}", answer);
+    assert_eq!(answer.data().next(), Some(&[192, 0, 2, 1][..]));
+    let answer = ctx.resolve("www.signed.example", 28, 1).unwrap();
+    assert!(answer.secure() && !answer.havedata(), "{}", answer);
+}
@@ -0,0 +1,360 @@
+//! Resolution of socket addresses and connection establishment.
+use std::io;
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::vec;
+
+use super::ip::answer_addrs;
+use super::rfc6724;
+use super::{Answer, AsyncID, Context, Error, IpStrategy, Result};
+
+// Recommended values from RFC 8305 section 8.
+const RESOLUTION_DELAY: Duration = Duration::from_millis(50);
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+// The default overall limit on `HostPort::connect`.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A host and port that resolves to socket addresses through a
+/// [Context](struct.Context.html), for use wherever
+/// [ToSocketAddrs](https://doc.rust-lang.org/std/net/trait.ToSocketAddrs.html)
+/// is accepted.
+///
+/// Addresses are ordered by
+/// [RFC 6724](https://tools.ietf.org/html/rfc6724) destination address
+/// selection using the host's local addresses.
+#[derive(Clone, Copy)]
+pub struct HostPort<'a> {
+    ctx: &'a Context,
+    host: &'a str,
+    port: u16,
+    strategy: IpStrategy,
+    require_secure: bool,
+    timeout: Duration,
+}
+
+impl<'a> HostPort<'a> {
+    /// Set which address families are looked up. Defaults to both.
+    pub fn strategy(mut self, strategy: IpStrategy) -> HostPort<'a> {
+        self.strategy = strategy;
+        self
+    }
+    /// Require that the answers be DNSSEC secure. Defaults to false.
+    pub fn require_secure(mut self, require_secure: bool) -> HostPort<'a> {
+        self.require_secure = require_secure;
+        self
+    }
+    /// Set the overall time limit of `connect`, covering both the queries and
+    /// the connection attempts. Defaults to 30 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> HostPort<'a> {
+        self.timeout = timeout;
+        self
+    }
+    /// Returns the host.
+    pub fn host(&self) -> &str {
+        self.host
+    }
+    /// Returns the port.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+    /// Resolve the host to socket addresses in order of preference. An IP address
+    /// is returned as is.
+    pub fn resolve(&self) -> Result<Vec<SocketAddr>> {
+        if let Ok(addr) = self.host.parse::<IpAddr>() {
+            return Ok(vec![SocketAddr::new(addr, self.port)]);
+        }
+        let lookup = self.ctx.lookup_ip_with(self.host, self.strategy)?;
+        if self.require_secure && !lookup.is_secure() {
+            return Err(super::Error::Insecure);
+        }
+        let mut addrs: Vec<_> = lookup
+            .iter()
+            .map(|addr| SocketAddr::new(addr, self.port))
+            .collect();
+        rfc6724::sort(&mut addrs);
+        Ok(addrs)
+    }
+    /// Connect to the host using Happy Eyeballs
+    /// ([RFC 8305](https://tools.ietf.org/html/rfc8305)).
+    ///
+    /// AAAA and A queries are made concurrently. Connection attempts start once
+    /// the AAAA answer arrives, or shortly after the A answer if the AAAA answer
+    /// is slow. Attempts alternate between address families and are staggered,
+    /// with the next attempt starting early if one fails. The first connection
+    /// established is returned and the remaining attempts are abandoned. Fails
+    /// with `io::ErrorKind::TimedOut` if no connection is established within the
+    /// timeout.
+    pub fn connect(&self) -> io::Result<TcpStream> {
+        if let Ok(addr) = self.host.parse::<IpAddr>() {
+            return TcpStream::connect_timeout(&SocketAddr::new(addr, self.port), self.timeout);
+        }
+        let deadline = Instant::now() + self.timeout;
+        let rrtypes: &[u16] = match self.strategy {
+            IpStrategy::Ipv4Only => &[1],
+            IpStrategy::Ipv6Only => &[28],
+            IpStrategy::Both => &[28, 1],
+        };
+        let (tx, rx) = mpsc::channel();
+        let mut ids: Vec<AsyncID> = Vec::with_capacity(rrtypes.len());
+        for &rrtype in rrtypes {
+            let tx = tx.clone();
+            let submitted = self.ctx.resolve_async_with_deadline(
+                self.host,
+                rrtype,
+                1,
+                deadline,
+                move |_, result| {
+                    let _ = tx.send(Event::Resolved(rrtype, result));
+                },
+            );
+            match submitted {
+                Ok(id) => ids.push(id),
+                Err(err) => {
+                    for id in ids {
+                        self.ctx.cancel(id);
+                    }
+                    return Err(err.into());
+                }
+            }
+        }
+        let mut race = Race {
+            port: self.port,
+            require_secure: self.require_secure,
+            lookups: rrtypes.len(),
+            aaaa_pending: rrtypes.contains(&28),
+            start_at: None,
+            untried: Vec::new(),
+            attempts: 0,
+            next_attempt: Instant::now(),
+            deadline,
+            error: None,
+            tx,
+        };
+        let result = self.ctx.process_until(|| race.step(&rx));
+        for id in ids {
+            self.ctx.cancel(id);
+        }
+        result?
+    }
+}
+
+enum Event {
+    Resolved(u16, Result<Answer>),
+    Connected(io::Result<TcpStream>),
+}
+
+// The state of a Happy Eyeballs connection race.
+struct Race {
+    port: u16,
+    require_secure: bool,
+    lookups: usize,
+    aaaa_pending: bool,
+    // When connection attempts may start.
+    start_at: Option<Instant>,
+    untried: Vec<SocketAddr>,
+    attempts: usize,
+    next_attempt: Instant,
+    deadline: Instant,
+    error: Option<io::Error>,
+    tx: mpsc::Sender<Event>,
+}
+
+impl Race {
+    fn step(&mut self, rx: &mpsc::Receiver<Event>) -> Option<io::Result<TcpStream>> {
+        let now = Instant::now();
+        while let Ok(event) = rx.try_recv() {
+            match event {
+                Event::Resolved(rrtype, result) => {
+                    self.lookups -= 1;
+                    if rrtype == 28 {
+                        self.aaaa_pending = false;
+                        self.start_at = Some(now);
+                    } else if self.aaaa_pending {
+                        self.start_at.get_or_insert(now + RESOLUTION_DELAY);
+                    } else {
+                        self.start_at = Some(now);
+                    }
+                    self.resolved(result);
+                }
+                Event::Connected(Ok(stream)) => return Some(Ok(stream)),
+                Event::Connected(Err(err)) => {
+                    self.attempts -= 1;
+                    self.next_attempt = now;
+                    self.error = Some(err);
+                }
+            }
+        }
+        if now >= self.deadline {
+            return Some(Err(Error::TimedOut.into()));
+        }
+        let started = self.start_at.is_some_and(|t| t <= now);
+        if started && !self.untried.is_empty() && (self.attempts == 0 || self.next_attempt <= now) {
+            let addr = self.untried.remove(0);
+            let tx = self.tx.clone();
+            let timeout = self.deadline - now;
+            thread::spawn(move || {
+                let _ = tx.send(Event::Connected(TcpStream::connect_timeout(&addr, timeout)));
+            });
+            self.attempts += 1;
+            self.next_attempt = now + CONNECTION_ATTEMPT_DELAY;
+        }
+        if self.lookups == 0 && self.untried.is_empty() && self.attempts == 0 {
+            return Some(Err(self.error.take().unwrap_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "no addresses found")
+            })));
+        }
+        None
+    }
+    fn resolved(&mut self, result: Result<Answer>) {
+        let answer = match result {
+            Ok(ref answer) if self.require_secure && !answer.security().is_secure() => {
+                self.error = Some(Error::Insecure.into());
+                return;
+            }
+            Ok(answer) => answer,
+            Err(err) => {
+                self.error = Some(err.into());
+                return;
+            }
+        };
+        let port = self.port;
+        self.untried.extend(
+            answer_addrs(&answer)
+                .into_iter()
+                .map(|addr| SocketAddr::new(addr, port)),
+        );
+        rfc6724::sort(&mut self.untried);
+        self.untried = interleave(&self.untried);
+    }
+}
+
+// Alternate address families, starting with the family of the first address.
+fn interleave(addrs: &[SocketAddr]) -> Vec<SocketAddr> {
+    let first = match addrs.first() {
+        Some(addr) => addr.is_ipv6(),
+        None => return Vec::new(),
+    };
+    let mut preferred = addrs.iter().filter(|a| a.is_ipv6() == first);
+    let mut other = addrs.iter().filter(|a| a.is_ipv6() != first);
+    let mut out = Vec::with_capacity(addrs.len());
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => return out,
+            (a, b) => out.extend(a.into_iter().chain(b).cloned()),
+        }
+    }
+}
+
+impl<'a> ToSocketAddrs for HostPort<'a> {
+    type Iter = vec::IntoIter<SocketAddr>;
+    fn to_socket_addrs(&self) -> io::Result<vec::IntoIter<SocketAddr>> {
+        Ok(self.resolve()?.into_iter())
+    }
+}
+
+impl Context {
+    /// Returns a [HostPort](struct.HostPort.html) that resolves `host` through
+    /// this context.
+    pub fn host_port<'a>(&'a self, host: &'a str, port: u16) -> HostPort<'a> {
+        HostPort {
+            ctx: self,
+            host,
+            port,
+            strategy: IpStrategy::Both,
+            require_secure: false,
+            timeout: CONNECT_TIMEOUT,
+        }
+    }
+    /// Connect to `host` using Happy Eyeballs. Equivalent to
+    /// `host_port(host, port).connect()`.
+    pub fn connect(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        self.host_port(host, port).connect()
+    }
+}
+
+#[test]
+fn test_host_port() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    let addrs: Vec<_> = ctx
+        .host_port("localhost", 80)
+        .strategy(IpStrategy::Ipv4Only)
+        .to_socket_addrs()
+        .unwrap()
+        .collect();
+    assert_eq!(addrs, [SocketAddr::from(([127, 0, 0, 1], 80))]);
+    let err = ctx
+        .host_port("localhost", 80)
+        .require_secure(true)
+        .to_socket_addrs()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let addrs: Vec<_> = ctx
+        .host_port("::1", 80)
+        .require_secure(true)
+        .to_socket_addrs()
+        .unwrap()
+        .collect();
+    assert_eq!(addrs, [SocketAddr::from(([0u16, 0, 0, 0, 0, 0, 0, 1], 80))]);
+}
+
+#[test]
+fn test_connect() {
+    use super::signed_zone::SignedZone;
+    use std::net::{Ipv4Addr, Ipv6Addr, TcpListener};
+    let mut zone = SignedZone::new("he.example.");
+    zone.add(
+        "both.he.example.",
+        28,
+        Ipv6Addr::LOCALHOST.octets().to_vec(),
+    );
+    zone.add("both.he.example.", 1, vec![127, 0, 0, 1]);
+    zone.add("v4.he.example.", 1, vec![127, 0, 0, 1]);
+    let ctx = zone.context();
+    ctx.async_via_thread().unwrap();
+    // Only IPv4 is listening, so the attempt on ::1 fails and IPv4 is tried.
+    let v4 = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = v4.local_addr().unwrap().port();
+    let stream = ctx
+        .host_port("both.he.example", port)
+        .require_secure(true)
+        .connect()
+        .unwrap();
+    assert_eq!(stream.peer_addr().unwrap(), v4.local_addr().unwrap());
+    let stream = ctx.connect("v4.he.example", port).unwrap();
+    assert_eq!(stream.peer_addr().unwrap(), v4.local_addr().unwrap());
+    // With both listening ::1 is preferred.
+    if let Ok(v6) = TcpListener::bind(("::1", port)) {
+        let stream = ctx.connect("both.he.example", port).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), v6.local_addr().unwrap());
+    }
+    let err = ctx.connect("none.he.example", port).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    drop(v4);
+    assert!(ctx.connect("v4.he.example", port).is_err());
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    // TEST-NET-1 (RFC 5737) shouldn't answer.
+    ctx.set_fwd4(Ipv4Addr::new(192, 0, 2, 1)).unwrap();
+    let err = ctx
+        .host_port("example.com", port)
+        .timeout(Duration::from_millis(100))
+        .connect()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert_eq!(
+        interleave(&[
+            SocketAddr::from(([0u16, 0, 0, 0, 0, 0, 0, 1], 1)),
+            SocketAddr::from(([0u16, 0, 0, 0, 0, 0, 0, 2], 1)),
+            SocketAddr::from(([127, 0, 0, 1], 1)),
+        ]),
+        [
+            SocketAddr::from(([0u16, 0, 0, 0, 0, 0, 0, 1], 1)),
+            SocketAddr::from(([127, 0, 0, 1], 1)),
+            SocketAddr::from(([0u16, 0, 0, 0, 0, 0, 0, 2], 1)),
+        ]
+    );
+}
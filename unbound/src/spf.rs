@@ -0,0 +1,819 @@
+//! Sender Policy Framework ([RFC 7208](https://tools.ietf.org/html/rfc7208)).
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::ip::answer_addrs;
+use super::reverse_name;
+use super::wire;
+use super::{Answer, Context, Security};
+
+// The most mechanisms and modifiers that cause DNS lookups in one evaluation.
+const MAX_LOOKUPS: usize = 10;
+// The most of those lookups that may return no records.
+const MAX_VOID_LOOKUPS: usize = 2;
+// The most names whose addresses are looked up for an mx or ptr mechanism.
+const MAX_NAMES: usize = 10;
+// The longest domain name looked up after macro expansion.
+const MAX_DOMAIN_LEN: usize = 253;
+
+// Macro letters allowed outside explanations.
+const DOMAIN_MACROS: &str = "slodipvh";
+
+/// The result of an SPF evaluation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SpfResult {
+    /// The domain has no SPF record, or no domain could be determined.
+    None,
+    /// The domain makes no assertion about the client.
+    Neutral,
+    /// The client is authorized to use the domain.
+    Pass,
+    /// The client is not authorized to use the domain.
+    Fail,
+    /// The client is probably not authorized to use the domain.
+    SoftFail,
+    /// A transient DNS error prevented evaluation.
+    TempError,
+    /// The domain's records could not be interpreted, or evaluation exceeded the
+    /// lookup limits.
+    PermError,
+}
+
+impl fmt::Display for SpfResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            SpfResult::None => "none",
+            SpfResult::Neutral => "neutral",
+            SpfResult::Pass => "pass",
+            SpfResult::Fail => "fail",
+            SpfResult::SoftFail => "softfail",
+            SpfResult::TempError => "temperror",
+            SpfResult::PermError => "permerror",
+        })
+    }
+}
+
+/// The outcome of an SPF evaluation, as returned by
+/// [Context::check_spf](struct.Context.html#method.check_spf).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpfCheck {
+    result: SpfResult,
+    explanation: Option<String>,
+    security: Security,
+}
+
+impl SpfCheck {
+    /// Returns the result.
+    pub fn result(&self) -> SpfResult {
+        self.result
+    }
+    /// Returns the explanation published by the domain for a `Fail` result.
+    pub fn explanation(&self) -> Option<&str> {
+        self.explanation.as_deref()
+    }
+    /// Returns the outcome of DNSSEC validation of the queries made, combined
+    /// as by [Security::downgrade](enum.Security.html#method.downgrade).
+    pub fn security(&self) -> &Security {
+        &self.security
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Mechanism {
+    All,
+    Include(String),
+    A(Option<String>, u8, u8),
+    Mx(Option<String>, u8, u8),
+    Ptr(Option<String>),
+    Ip4(Ipv4Addr, u8),
+    Ip6(Ipv6Addr, u8),
+    Exists(String),
+}
+
+// A parsed SPF record. Directives pair the result of a match with the mechanism.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Record {
+    directives: Vec<(SpfResult, Mechanism)>,
+    redirect: Option<String>,
+    exp: Option<String>,
+}
+
+// Returns true if TXT record text is an SPF version 1 record.
+fn is_spf(text: &[u8]) -> bool {
+    text.len() >= 6
+        && text[..6].eq_ignore_ascii_case(b"v=spf1")
+        && text.get(6).map_or(true, |&b| b == b' ')
+}
+
+// Expands the macros in a macro-string, looking up macro letters with `value`.
+// Returns `None` if the string is malformed or uses an unknown letter.
+fn expand<F>(spec: &str, value: F) -> Option<String>
+where
+    F: Fn(char) -> Option<String>,
+{
+    let mut out = String::with_capacity(spec.len());
+    let mut rest = spec;
+    while let Some(i) = rest.find('%') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        match rest.chars().next()? {
+            '%' => out.push('%'),
+            '_' => out.push(' '),
+            '-' => out.push_str("%20"),
+            '{' => {
+                let end = rest.find('}')?;
+                out.push_str(&expand_macro(&rest[1..end], &value)?);
+                rest = &rest[end + 1..];
+                continue;
+            }
+            _ => return None,
+        }
+        rest = &rest[1..];
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+// Expands the body of a `%{...}` macro: a letter, then optionally the number of
+// parts to keep, `r` to reverse the parts, and the delimiters to split on.
+fn expand_macro<F>(body: &str, value: &F) -> Option<String>
+where
+    F: Fn(char) -> Option<String>,
+{
+    let letter = body.chars().next()?;
+    let rest = &body[letter.len_utf8()..];
+    let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+    let keep = match digits {
+        0 => None,
+        _ => Some(rest[..digits].parse::<usize>().ok().filter(|&n| n > 0)?),
+    };
+    let rest = &rest[digits..];
+    let (reverse, delimiters) = if rest.starts_with('r') || rest.starts_with('R') {
+        (true, &rest[1..])
+    } else {
+        (false, rest)
+    };
+    if !delimiters.chars().all(|c| ".-+,/_=".contains(c)) {
+        return None;
+    }
+    let delimiters = if delimiters.is_empty() {
+        "."
+    } else {
+        delimiters
+    };
+    let value = value(letter.to_ascii_lowercase())?;
+    let mut parts: Vec<_> = value.split(|c| delimiters.contains(c)).collect();
+    if reverse {
+        parts.reverse();
+    }
+    if let Some(keep) = keep {
+        let skip = parts.len().saturating_sub(keep);
+        parts.drain(..skip);
+    }
+    let expanded = parts.join(".");
+    if !letter.is_ascii_uppercase() {
+        return Some(expanded);
+    }
+    let mut escaped = String::with_capacity(expanded.len());
+    for b in expanded.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                escaped.push(b as char)
+            }
+            _ => escaped.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    Some(escaped)
+}
+
+// Returns true if `spec` is a well formed macro-string using the given letters.
+fn is_macro_string(spec: &str, letters: &str) -> bool {
+    !spec.is_empty()
+        && spec.bytes().all(|b| (0x21..=0x7E).contains(&b))
+        && expand(spec, |c| {
+            if letters.contains(c) {
+                Some(String::new())
+            } else {
+                None
+            }
+        })
+        .is_some()
+}
+
+fn domain_spec(spec: &str) -> Option<String> {
+    if is_macro_string(spec, DOMAIN_MACROS) {
+        Some(spec.to_owned())
+    } else {
+        None
+    }
+}
+
+// Parses an optional `:domain-spec` argument.
+fn optional_domain_spec(arg: &str) -> Option<Option<String>> {
+    match arg.strip_prefix(':') {
+        Some(spec) => domain_spec(spec).map(Some),
+        None if arg.is_empty() => Some(None),
+        None => None,
+    }
+}
+
+fn parse_prefix(s: &str, max: u8) -> Option<u8> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) || (s.len() > 1 && s.starts_with('0'))
+    {
+        return None;
+    }
+    s.parse().ok().filter(|&n| n <= max)
+}
+
+// Splits the IPv4 and IPv6 prefix lengths from the end of an a or mx argument.
+fn split_cidr(arg: &str) -> Option<(&str, u8, u8)> {
+    let (arg, v6) = match arg.rfind("//") {
+        Some(i) => (&arg[..i], parse_prefix(&arg[i + 2..], 128)?),
+        None => (arg, 128),
+    };
+    let (arg, v4) = match arg.rfind('/') {
+        Some(i) => (&arg[..i], parse_prefix(&arg[i + 1..], 32)?),
+        None => (arg, 32),
+    };
+    Some((arg, v4, v6))
+}
+
+// Splits a modifier into its name and value.
+fn modifier(term: &str) -> Option<(&str, &str)> {
+    let eq = term.find('=')?;
+    let name = &term[..eq];
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_' || b == b'.');
+    if valid {
+        Some((name, &term[eq + 1..]))
+    } else {
+        None
+    }
+}
+
+impl Record {
+    // Parses the text of an SPF record. Returns `None` on a syntax error.
+    fn parse(text: &str) -> Option<Record> {
+        let mut terms = text.split(' ').filter(|t| !t.is_empty());
+        if !terms.next()?.eq_ignore_ascii_case("v=spf1") {
+            return None;
+        }
+        let mut record = Record {
+            directives: Vec::new(),
+            redirect: None,
+            exp: None,
+        };
+        for term in terms {
+            if let Some((name, value)) = modifier(term) {
+                let slot = if name.eq_ignore_ascii_case("redirect") {
+                    &mut record.redirect
+                } else if name.eq_ignore_ascii_case("exp") {
+                    &mut record.exp
+                } else if value.is_empty() || is_macro_string(value, DOMAIN_MACROS) {
+                    continue;
+                } else {
+                    return None;
+                };
+                if slot.is_some() {
+                    return None;
+                }
+                *slot = Some(domain_spec(value)?);
+                continue;
+            }
+            let (qualifier, term) = match term.as_bytes()[0] {
+                b'+' => (SpfResult::Pass, &term[1..]),
+                b'-' => (SpfResult::Fail, &term[1..]),
+                b'~' => (SpfResult::SoftFail, &term[1..]),
+                b'?' => (SpfResult::Neutral, &term[1..]),
+                _ => (SpfResult::Pass, term),
+            };
+            let (name, arg) = match term.find([':', '/']) {
+                Some(i) => (&term[..i], &term[i..]),
+                None => (term, ""),
+            };
+            let mechanism = match &name.to_ascii_lowercase()[..] {
+                "all" if arg.is_empty() => Mechanism::All,
+                "include" => Mechanism::Include(domain_spec(arg.strip_prefix(':')?)?),
+                "exists" => Mechanism::Exists(domain_spec(arg.strip_prefix(':')?)?),
+                "ptr" => Mechanism::Ptr(optional_domain_spec(arg)?),
+                "a" => {
+                    let (spec, v4, v6) = split_cidr(arg)?;
+                    Mechanism::A(optional_domain_spec(spec)?, v4, v6)
+                }
+                "mx" => {
+                    let (spec, v4, v6) = split_cidr(arg)?;
+                    Mechanism::Mx(optional_domain_spec(spec)?, v4, v6)
+                }
+                "ip4" => {
+                    let arg = arg.strip_prefix(':')?;
+                    let (addr, prefix) = match arg.find('/') {
+                        Some(i) => (&arg[..i], parse_prefix(&arg[i + 1..], 32)?),
+                        None => (arg, 32),
+                    };
+                    Mechanism::Ip4(addr.parse().ok()?, prefix)
+                }
+                "ip6" => {
+                    let arg = arg.strip_prefix(':')?;
+                    let (addr, prefix) = match arg.find('/') {
+                        Some(i) => (&arg[..i], parse_prefix(&arg[i + 1..], 128)?),
+                        None => (arg, 128),
+                    };
+                    Mechanism::Ip6(addr.parse().ok()?, prefix)
+                }
+                _ => return None,
+            };
+            record.directives.push((qualifier, mechanism));
+        }
+        Some(record)
+    }
+}
+
+// Returns true if `addr` is within the network `net/prefix` of the same family.
+fn in_network(addr: IpAddr, net: IpAddr, prefix: u8) -> bool {
+    match (addr, net) {
+        (IpAddr::V4(addr), IpAddr::V4(net)) => {
+            let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+            u32::from(addr) & mask == u32::from(net) & mask
+        }
+        (IpAddr::V6(addr), IpAddr::V6(net)) => {
+            let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+            u128::from(addr) & mask == u128::from(net) & mask
+        }
+        _ => false,
+    }
+}
+
+// Returns true if `domain` is a fully qualified name with valid label lengths.
+fn is_valid_domain(domain: &str) -> bool {
+    let domain = domain.trim_end_matches('.');
+    domain.len() <= MAX_DOMAIN_LEN
+        && domain.contains('.')
+        && domain.split('.').all(|l| !l.is_empty() && l.len() <= 63)
+}
+
+// The state of an evaluation of check_host() and the queries it makes.
+struct Evaluator<'a> {
+    ctx: &'a Context,
+    ip: IpAddr,
+    sender: String,
+    helo: String,
+    lookups: usize,
+    voids: usize,
+    security: Security,
+}
+
+impl<'a> Evaluator<'a> {
+    // Resolves queries concurrently, recording the security of the answers.
+    fn resolve(&mut self, queries: &[(&str, u16, u16)]) -> Vec<super::Result<Answer>> {
+        let results = match self.ctx.resolve_all(queries) {
+            Ok(results) => results,
+            Err(err) => queries.iter().map(|_| Err(err.clone())).collect(),
+        };
+        results
+            .into_iter()
+            .map(|result| {
+                let answer = result.and_then(Answer::checked)?;
+                self.security.downgrade(answer.security());
+                Ok(answer)
+            })
+            .collect()
+    }
+    // Resolves the query of a mechanism, counting void lookups.
+    fn lookup(&mut self, name: &str, rrtype: u16) -> Result<Answer, SpfResult> {
+        let answer = self.resolve(&[(name, rrtype, 1)]).remove(0);
+        let answer = answer.map_err(|_| SpfResult::TempError)?;
+        if !answer.havedata() {
+            self.voids += 1;
+            if self.voids > MAX_VOID_LOOKUPS {
+                return Err(SpfResult::PermError);
+            }
+        }
+        Ok(answer)
+    }
+    // Counts a mechanism or modifier that causes DNS lookups.
+    fn count(&mut self) -> Result<(), SpfResult> {
+        self.lookups += 1;
+        if self.lookups > MAX_LOOKUPS {
+            Err(SpfResult::PermError)
+        } else {
+            Ok(())
+        }
+    }
+    // Returns the address record type matching the client's address family.
+    fn rrtype(&self) -> u16 {
+        if self.ip.is_ipv4() {
+            1
+        } else {
+            28
+        }
+    }
+    fn macro_value(&self, letter: char, domain: &str, explain: bool) -> Option<String> {
+        let at = self.sender.rfind('@').unwrap_or(0);
+        Some(match letter {
+            's' => self.sender.clone(),
+            'l' => self.sender[..at].to_owned(),
+            'o' => self.sender[at + 1..].to_owned(),
+            'd' => domain.to_owned(),
+            'i' => match self.ip {
+                IpAddr::V4(ip) => ip.to_string(),
+                IpAddr::V6(ip) => {
+                    let nibbles: Vec<_> = ip
+                        .octets()
+                        .iter()
+                        .flat_map(|b| vec![format!("{:x}", b >> 4), format!("{:x}", b & 0xF)])
+                        .collect();
+                    nibbles.join(".")
+                }
+            },
+            // Validating the client's name would take further lookups, which
+            // RFC 7208 discourages.
+            'p' => "unknown".to_owned(),
+            'v' if self.ip.is_ipv4() => "in-addr".to_owned(),
+            'v' => "ip6".to_owned(),
+            'h' => self.helo.clone(),
+            'c' if explain => self.ip.to_string(),
+            'r' if explain => "unknown".to_owned(),
+            't' if explain => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+                .to_string(),
+            _ => return None,
+        })
+    }
+    // Expands a domain-spec, or returns `domain` if there is none, truncating the
+    // result to a name that can be looked up.
+    fn target(&self, spec: Option<&str>, domain: &str) -> Result<String, SpfResult> {
+        let mut name = match spec {
+            Some(spec) => {
+                expand(spec, |c| self.macro_value(c, domain, false)).ok_or(SpfResult::PermError)?
+            }
+            None => domain.to_owned(),
+        };
+        while name.trim_end_matches('.').len() > MAX_DOMAIN_LEN {
+            name = match name.find('.') {
+                Some(i) => name[i + 1..].to_owned(),
+                None => return Err(SpfResult::PermError),
+            };
+        }
+        if name.trim_end_matches('.').is_empty() {
+            return Err(SpfResult::PermError);
+        }
+        Ok(name)
+    }
+    // Returns true if any address is within the client's network.
+    fn any_in_network(&self, addrs: &[IpAddr], v4: u8, v6: u8) -> bool {
+        addrs
+            .iter()
+            .any(|&addr| in_network(self.ip, addr, if addr.is_ipv4() { v4 } else { v6 }))
+    }
+    fn matches(&mut self, mechanism: &Mechanism, domain: &str) -> Result<bool, SpfResult> {
+        match *mechanism {
+            Mechanism::All => Ok(true),
+            Mechanism::Ip4(net, prefix) => Ok(in_network(self.ip, IpAddr::V4(net), prefix)),
+            Mechanism::Ip6(net, prefix) => Ok(in_network(self.ip, IpAddr::V6(net), prefix)),
+            Mechanism::Include(ref spec) => {
+                self.count()?;
+                let name = self.target(Some(spec), domain)?;
+                match self.check_host(&name).0 {
+                    SpfResult::Pass => Ok(true),
+                    SpfResult::Fail | SpfResult::SoftFail | SpfResult::Neutral => Ok(false),
+                    SpfResult::TempError => Err(SpfResult::TempError),
+                    SpfResult::None | SpfResult::PermError => Err(SpfResult::PermError),
+                }
+            }
+            Mechanism::A(ref spec, v4, v6) => {
+                self.count()?;
+                let name = self.target(spec.as_deref(), domain)?;
+                let rrtype = self.rrtype();
+                let answer = self.lookup(&name, rrtype)?;
+                Ok(self.any_in_network(&answer_addrs(&answer), v4, v6))
+            }
+            Mechanism::Mx(ref spec, v4, v6) => {
+                self.count()?;
+                let name = self.target(spec.as_deref(), domain)?;
+                let answer = self.lookup(&name, 15)?;
+                let exchanges: Vec<_> = answer
+                    .data()
+                    .filter_map(|data| wire::read_rdata_name(data, 2))
+                    .map(|(name, _)| name)
+                    .filter(|name| name != ".")
+                    .collect();
+                if exchanges.len() > MAX_NAMES {
+                    return Err(SpfResult::PermError);
+                }
+                let rrtype = self.rrtype();
+                let queries: Vec<_> = exchanges.iter().map(|n| (&n[..], rrtype, 1)).collect();
+                for result in self.resolve(&queries) {
+                    let answer = result.map_err(|_| SpfResult::TempError)?;
+                    if self.any_in_network(&answer_addrs(&answer), v4, v6) {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Mechanism::Ptr(ref spec) => {
+                self.count()?;
+                let target = self.target(spec.as_deref(), domain)?;
+                let target = target.trim_end_matches('.').to_ascii_lowercase();
+                // A failed PTR lookup is no match rather than an error.
+                let answer = match self.lookup(&reverse_name(&self.ip), 12) {
+                    Ok(answer) => answer,
+                    Err(SpfResult::TempError) => return Ok(false),
+                    Err(result) => return Err(result),
+                };
+                let names: Vec<_> = answer
+                    .data()
+                    .filter_map(|data| wire::read_rdata_name(data, 0))
+                    .map(|(name, _)| name.trim_end_matches('.').to_ascii_lowercase())
+                    .take(MAX_NAMES)
+                    .collect();
+                let rrtype = self.rrtype();
+                let queries: Vec<_> = names.iter().map(|n| (&n[..], rrtype, 1)).collect();
+                let ip = self.ip;
+                Ok(names
+                    .iter()
+                    .zip(self.resolve(&queries))
+                    .any(|(name, result)| {
+                        (*name == target || name.ends_with(&format!(".{}", target)))
+                            && result.is_ok_and(|answer| answer_addrs(&answer).contains(&ip))
+                    }))
+            }
+            Mechanism::Exists(ref spec) => {
+                self.count()?;
+                let name = self.target(Some(spec), domain)?;
+                Ok(self.lookup(&name, 1)?.havedata())
+            }
+        }
+    }
+    // Looks up and parses the SPF record of a domain.
+    fn record(&mut self, domain: &str) -> Result<Record, SpfResult> {
+        let answer = self.resolve(&[(domain, 16, 1)]).remove(0);
+        let answer = answer.map_err(|_| SpfResult::TempError)?;
+        let mut records = answer
+            .data()
+            .filter_map(wire::read_txt)
+            .filter(|text| is_spf(text));
+        match (records.next(), records.next()) {
+            (None, _) => Err(SpfResult::None),
+            (Some(text), None) => String::from_utf8(text)
+                .ok()
+                .and_then(|text| Record::parse(&text))
+                .ok_or(SpfResult::PermError),
+            _ => Err(SpfResult::PermError),
+        }
+    }
+    // Looks up and expands the explanation for a Fail result.
+    fn explain(&mut self, spec: &str, domain: &str) -> Option<String> {
+        let name = self.target(Some(spec), domain).ok()?;
+        let answer = self.resolve(&[(&name, 16, 1)]).remove(0).ok()?;
+        let mut texts = answer.data().filter_map(wire::read_txt);
+        match (texts.next(), texts.next()) {
+            (Some(text), None) => {
+                let text = String::from_utf8(text).ok()?;
+                expand(&text, |c| self.macro_value(c, domain, true))
+            }
+            _ => None,
+        }
+    }
+    // The check_host() function of RFC 7208, returning the result and any
+    // explanation.
+    fn check_host(&mut self, domain: &str) -> (SpfResult, Option<String>) {
+        if !is_valid_domain(domain) {
+            return (SpfResult::None, None);
+        }
+        let record = match self.record(domain) {
+            Ok(record) => record,
+            Err(result) => return (result, None),
+        };
+        for &(qualifier, ref mechanism) in &record.directives {
+            match self.matches(mechanism, domain) {
+                Ok(true) => {
+                    let explanation = match record.exp {
+                        Some(ref exp) if qualifier == SpfResult::Fail => self.explain(exp, domain),
+                        _ => None,
+                    };
+                    return (qualifier, explanation);
+                }
+                Ok(false) => {}
+                Err(result) => return (result, None),
+            }
+        }
+        let redirect = match record.redirect {
+            Some(ref spec) => spec,
+            None => return (SpfResult::Neutral, None),
+        };
+        let name = match self
+            .count()
+            .and_then(|_| self.target(Some(redirect), domain))
+        {
+            Ok(name) => name,
+            Err(result) => return (result, None),
+        };
+        match self.check_host(&name) {
+            (SpfResult::None, _) => (SpfResult::PermError, None),
+            outcome => outcome,
+        }
+    }
+}
+
+impl Context {
+    /// Evaluate the SPF policy of a sender for a client address.
+    ///
+    /// `sender` is the MAIL FROM address and `helo` the name the client gave in
+    /// its HELO or EHLO command. An empty sender, as used by bounces, is replaced
+    /// by `postmaster@helo`, so the same call checks the HELO identity. The
+    /// lookups are made asynchronously, concurrently where a mechanism needs the
+    /// addresses of several names, and at most 10 mechanisms and modifiers that
+    /// cause lookups are evaluated. The `%{p}` macro always expands to
+    /// `unknown`.
+    pub fn check_spf(&self, ip: IpAddr, sender: &str, helo: &str) -> SpfCheck {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            ip => ip,
+        };
+        let sender = match sender.rfind('@') {
+            _ if sender.is_empty() => format!("postmaster@{}", helo),
+            Some(0) => format!("postmaster{}", sender),
+            Some(_) => sender.to_owned(),
+            None => format!("postmaster@{}", sender),
+        };
+        let mut evaluator = Evaluator {
+            ctx: self,
+            ip,
+            sender,
+            helo: helo.to_owned(),
+            lookups: 0,
+            voids: 0,
+            security: Security::Secure,
+        };
+        let domain = evaluator.sender[evaluator.sender.rfind('@').unwrap() + 1..].to_owned();
+        let (result, explanation) = evaluator.check_host(&domain);
+        SpfCheck {
+            result,
+            explanation,
+            security: evaluator.security,
+        }
+    }
+}
+
+#[test]
+fn test_spf_macros() {
+    let ctx = Context::new().unwrap();
+    let mut evaluator = Evaluator {
+        ctx: &ctx,
+        ip: "192.0.2.3".parse().unwrap(),
+        sender: "strong-bad@email.example.com".to_owned(),
+        helo: "mx.example.org".to_owned(),
+        lookups: 0,
+        voids: 0,
+        security: Security::Secure,
+    };
+    let domain = "email.example.com";
+    let expand_domain =
+        |e: &Evaluator, spec: &str| expand(spec, |c| e.macro_value(c, domain, false));
+    // Examples from RFC 7208, section 7.4.
+    for &(spec, expanded) in &[
+        ("%{s}", "strong-bad@email.example.com"),
+        ("%{o}", "email.example.com"),
+        ("%{d4}", "email.example.com"),
+        ("%{d3}", "email.example.com"),
+        ("%{d2}", "example.com"),
+        ("%{d1}", "com"),
+        ("%{dr}", "com.example.email"),
+        ("%{d2r}", "example.email"),
+        ("%{l}", "strong-bad"),
+        ("%{l-}", "strong.bad"),
+        ("%{lr}", "strong-bad"),
+        ("%{lr-}", "bad.strong"),
+        ("%{l1r-}", "strong"),
+        (
+            "%{ir}.%{v}._spf.%{d2}",
+            "3.2.0.192.in-addr._spf.example.com",
+        ),
+        ("%{lr-}.lp._spf.%{d2}", "bad.strong.lp._spf.example.com"),
+        (
+            "%{d2}.trusted-domains.example.net",
+            "example.com.trusted-domains.example.net",
+        ),
+        ("%{L}%_%%%-", "strong-bad %%20"),
+    ] {
+        assert_eq!(expand_domain(&evaluator, spec).as_deref(), Some(expanded));
+    }
+    assert_eq!(expand_domain(&evaluator, "%{c}"), None);
+    assert_eq!(expand_domain(&evaluator, "%{d0}"), None);
+    assert_eq!(expand_domain(&evaluator, "%x"), None);
+    evaluator.ip = "2001:db8::cb01".parse().unwrap();
+    assert_eq!(
+        expand_domain(&evaluator, "%{ir}.%{v}._spf.%{d2}").as_deref(),
+        Some(
+            "1.0.b.c.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6._spf.example.com"
+        )
+    );
+    assert!(Record::parse("v=spf1 a:%{d").is_none());
+    assert!(Record::parse("v=spf1 redirect=a.example redirect=b.example").is_none());
+    assert!(Record::parse("v=spf1 ip4:192.0.2.0/33").is_none());
+    assert!(Record::parse("v=spf1 foo").is_none());
+    assert_eq!(
+        Record::parse("v=spf1 -a:%{d}/24//64 ~mx unknown=x ?ip6:2001:db8::/32").unwrap(),
+        Record {
+            directives: vec![
+                (
+                    SpfResult::Fail,
+                    Mechanism::A(Some("%{d}".to_owned()), 24, 64)
+                ),
+                (SpfResult::SoftFail, Mechanism::Mx(None, 32, 128)),
+                (
+                    SpfResult::Neutral,
+                    Mechanism::Ip6("2001:db8::".parse().unwrap(), 32)
+                ),
+            ],
+            redirect: None,
+            exp: None,
+        }
+    );
+}
+
+#[test]
+fn test_check_spf() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    ctx.zone_add("spf.test.", "static").unwrap();
+    for data in &[
+        "spf.test. TXT \"v=spf1 ip4:192.0.2.0/24 include:inc.spf.test \" \"a mx:mx.spf.test/30 -all\"",
+        "spf.test. TXT \"unrelated\"",
+        "spf.test. A 198.51.100.1",
+        "inc.spf.test. TXT \"v=spf1 ip6:2001:db8::/32 -all\"",
+        "mx.spf.test. MX 10 mail.spf.test.",
+        "mail.spf.test. A 203.0.113.4",
+        "soft.spf.test. TXT \"v=spf1 exists:%{ir}.allow.spf.test ~all\"",
+        "1.0.0.10.allow.spf.test. A 127.0.0.2",
+        "redir.spf.test. TXT \"v=spf1 redirect=spf.test\"",
+        "exp.spf.test. TXT \"v=spf1 -all exp=why.spf.test\"",
+        "why.spf.test. TXT \"%{i} is not one of %{d}'s servers\"",
+        "two.spf.test. TXT \"v=spf1 +all\"",
+        "two.spf.test. TXT \"v=spf1 -all\"",
+        "loop.spf.test. TXT \"v=spf1 include:loop.spf.test\"",
+        "void.spf.test. TXT \"v=spf1 a:x.spf.test a:y.spf.test a:z.spf.test +all\"",
+    ] {
+        ctx.data_add(data).unwrap();
+    }
+    let check = |ip: &str, sender: &str| ctx.check_spf(ip.parse().unwrap(), sender, "helo.test");
+    assert_eq!(
+        check("192.0.2.1", "user@spf.test").result(),
+        SpfResult::Pass
+    );
+    assert_eq!(
+        check("2001:db8::1", "user@spf.test").result(),
+        SpfResult::Pass
+    );
+    assert_eq!(
+        check("198.51.100.1", "user@spf.test").result(),
+        SpfResult::Pass
+    );
+    assert_eq!(
+        check("203.0.113.6", "user@spf.test").result(),
+        SpfResult::Pass
+    );
+    assert_eq!(
+        check("203.0.113.8", "user@spf.test").result(),
+        SpfResult::Fail
+    );
+    assert_eq!(
+        check("10.0.0.1", "user@soft.spf.test").result(),
+        SpfResult::Pass
+    );
+    assert_eq!(
+        check("10.0.0.2", "user@soft.spf.test").result(),
+        SpfResult::SoftFail
+    );
+    assert_eq!(
+        check("192.0.2.1", "redir.spf.test").result(),
+        SpfResult::Pass
+    );
+    assert_eq!(
+        check("192.0.2.1", "user@none.spf.test").result(),
+        SpfResult::None
+    );
+    assert_eq!(
+        check("192.0.2.1", "user@two.spf.test").result(),
+        SpfResult::PermError
+    );
+    assert_eq!(
+        check("192.0.2.1", "user@loop.spf.test").result(),
+        SpfResult::PermError
+    );
+    assert_eq!(
+        check("192.0.2.1", "user@void.spf.test").result(),
+        SpfResult::PermError
+    );
+    let exp = check("192.0.2.1", "user@exp.spf.test");
+    assert_eq!(exp.result(), SpfResult::Fail);
+    assert_eq!(
+        exp.explanation(),
+        Some("192.0.2.1 is not one of exp.spf.test's servers")
+    );
+    assert!(!exp.security().is_secure());
+}
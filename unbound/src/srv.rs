@@ -0,0 +1,231 @@
+//! Service location ([RFC 2782](https://tools.ietf.org/html/rfc2782)).
+use std::net::SocketAddr;
+use std::vec;
+
+use super::random;
+use super::rfc6724;
+use super::wire;
+use super::{ip, Context, LookupIp, Result, Security};
+
+/// A target of a service, as returned by
+/// [Context::lookup_srv](struct.Context.html#method.lookup_srv).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SrvTarget {
+    priority: u16,
+    weight: u16,
+    port: u16,
+    target: String,
+    addrs: Result<LookupIp>,
+}
+
+impl SrvTarget {
+    /// Returns the priority of the target. Lower values are preferred.
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+    /// Returns the weight of the target relative to others of the same priority.
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+    /// Returns the port of the service on the target.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+    /// Returns the name of the target in presentation format with a trailing dot.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+    /// Returns the addresses of the target, or the error that prevented them
+    /// being looked up.
+    pub fn addrs(&self) -> Result<&LookupIp> {
+        self.addrs.as_ref().map_err(Clone::clone)
+    }
+}
+
+/// The targets of a service.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupSrv {
+    targets: Vec<SrvTarget>,
+    unavailable: bool,
+    security: Security,
+}
+
+impl LookupSrv {
+    /// Returns the targets in the order they should be tried.
+    pub fn targets(&self) -> &[SrvTarget] {
+        &self.targets
+    }
+    /// Returns true if the service is decidedly not available, indicated by a
+    /// single SRV record with a target of ".".
+    pub fn is_unavailable(&self) -> bool {
+        self.unavailable
+    }
+    /// Returns the outcome of DNSSEC validation of the SRV query.
+    pub fn security(&self) -> &Security {
+        &self.security
+    }
+    /// Returns the socket addresses of the targets in the order they should be
+    /// tried. The addresses of each target are ordered by
+    /// [RFC 6724](https://tools.ietf.org/html/rfc6724).
+    pub fn socket_addrs(&self) -> vec::IntoIter<SocketAddr> {
+        let mut out = Vec::new();
+        for target in &self.targets {
+            if let Ok(ref lookup) = target.addrs {
+                let start = out.len();
+                out.extend(lookup.iter().map(|addr| SocketAddr::new(addr, target.port)));
+                rfc6724::sort(&mut out[start..]);
+            }
+        }
+        out.into_iter()
+    }
+}
+
+// Priority, weight, port and target of an SRV record.
+pub(crate) type Srv = (u16, u16, u16, String);
+
+// Parses the data of an SRV record.
+pub(crate) fn parse(data: &[u8]) -> Option<Srv> {
+    let priority = wire::read_u16(data, 0)?;
+    let weight = wire::read_u16(data, 2)?;
+    let port = wire::read_u16(data, 4)?;
+    let (target, _) = wire::read_rdata_name(data, 6)?;
+    Some((priority, weight, port, target))
+}
+
+// Order records by priority, then by weighted random selection within each
+// priority as described in RFC 2782.
+pub(crate) fn order(mut records: Vec<Srv>) -> Vec<Srv> {
+    // Zero weight records are placed first within each priority.
+    records.sort_by_key(|&(priority, weight, _, _)| (priority, weight != 0));
+    let mut out = Vec::with_capacity(records.len());
+    while !records.is_empty() {
+        let priority = records[0].0;
+        let len = records.iter().take_while(|r| r.0 == priority).count();
+        let mut group: Vec<_> = records.drain(..len).collect();
+        while !group.is_empty() {
+            let total: u64 = group.iter().map(|r| u64::from(r.1)).sum();
+            let choice = random::below(total + 1);
+            let mut running = 0;
+            let i = group
+                .iter()
+                .position(|r| {
+                    running += u64::from(r.1);
+                    running >= choice
+                })
+                .unwrap_or(0);
+            out.push(group.remove(i));
+        }
+    }
+    out
+}
+
+impl Context {
+    /// Look up the targets of a service. An underscore is prepended to `service`
+    /// and `proto` if not present, so `("http", "tcp", "example.com")` queries
+    /// `_http._tcp.example.com`. The addresses of the targets are looked up
+    /// concurrently.
+    pub fn lookup_srv(&self, service: &str, proto: &str, name: &str) -> Result<LookupSrv> {
+        let label = |s: &str| {
+            if s.starts_with('_') {
+                s.to_owned()
+            } else {
+                format!("_{}", s)
+            }
+        };
+        let qname = format!("{}.{}.{}", label(service), label(proto), name);
+        let answer = self.resolve(&qname, 33, 1)?.checked()?;
+        let security = answer.security();
+        let records: Vec<Srv> = answer.data().filter_map(parse).collect();
+        if records.len() == 1 && records[0].3 == "." {
+            return Ok(LookupSrv {
+                targets: Vec::new(),
+                unavailable: true,
+                security,
+            });
+        }
+        let records = order(records);
+        let queries: Vec<_> = records
+            .iter()
+            .flat_map(|(_, _, _, target)| vec![(&target[..], 28, 1), (&target[..], 1, 1)])
+            .collect();
+        let mut results = self.resolve_all(&queries)?.into_iter();
+        let targets = records
+            .into_iter()
+            .map(|(priority, weight, port, target)| {
+                let answers: Vec<_> = results.by_ref().take(2).collect();
+                SrvTarget {
+                    priority,
+                    weight,
+                    port,
+                    target,
+                    addrs: ip::from_answers([28, 1].iter().cloned().zip(answers)),
+                }
+            })
+            .collect();
+        Ok(LookupSrv {
+            targets,
+            unavailable: false,
+            security,
+        })
+    }
+}
+
+#[test]
+fn test_lookup_srv() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    ctx.zone_add("srv.test.", "static").unwrap();
+    ctx.data_add("_sip._udp.srv.test. SRV 20 0 5060 b.srv.test.")
+        .unwrap();
+    ctx.data_add("_sip._udp.srv.test. SRV 10 0 5061 a.srv.test.")
+        .unwrap();
+    ctx.data_add("a.srv.test. A 192.0.2.1").unwrap();
+    ctx.data_add("b.srv.test. A 192.0.2.2").unwrap();
+    ctx.data_add("_sip._tcp.srv.test. SRV 0 0 0 .").unwrap();
+    let srv = ctx.lookup_srv("sip", "udp", "srv.test").unwrap();
+    let addrs: Vec<_> = srv.socket_addrs().collect();
+    assert_eq!(
+        addrs,
+        [
+            SocketAddr::from(([192, 0, 2, 1], 5061)),
+            SocketAddr::from(([192, 0, 2, 2], 5060)),
+        ]
+    );
+    assert!(ctx
+        .lookup_srv("_sip", "_tcp", "srv.test")
+        .unwrap()
+        .is_unavailable());
+    let records = vec![
+        (1, 0, 1, "z.".to_owned()),
+        (1, 65535, 2, "w.".to_owned()),
+        (0, 10, 3, "p.".to_owned()),
+    ];
+    let ordered = order(records);
+    assert_eq!(ordered[0].3, "p.");
+    assert_eq!(ordered.len(), 3);
+}
+
+#[test]
+fn test_lookup_srv_partial() {
+    use super::Error;
+    use std::time::Duration;
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    ctx.set_fwd4(std::net::Ipv4Addr::new(192, 0, 2, 1)).unwrap();
+    ctx.set_lookup_timeout(Duration::from_millis(100));
+    ctx.zone_add("srv.test.", "typetransparent").unwrap();
+    ctx.data_add("_sip._udp.srv.test. SRV 10 0 5061 a.srv.test.")
+        .unwrap();
+    ctx.data_add("_sip._udp.srv.test. SRV 20 0 5060 b.srv.test.")
+        .unwrap();
+    ctx.data_add("a.srv.test. A 192.0.2.1").unwrap();
+    ctx.data_add("b.srv.test. A 192.0.2.2").unwrap();
+    ctx.data_add("b.srv.test. AAAA 2001:db8::2").unwrap();
+    let srv = ctx.lookup_srv("sip", "udp", "srv.test").unwrap();
+    let first = srv.targets()[0].addrs().unwrap();
+    assert_eq!(first.ipv6_error(), Some(&Error::TimedOut));
+    assert_eq!(first.iter().count(), 1);
+    let second = srv.targets()[1].addrs().unwrap();
+    assert_eq!(second.ipv6_error(), None);
+    assert_eq!(second.iter().count(), 2);
+}
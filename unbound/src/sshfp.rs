@@ -0,0 +1,140 @@
+//! SSH host key fingerprints ([RFC 4255](https://tools.ietf.org/html/rfc4255),
+//! [RFC 6594](https://tools.ietf.org/html/rfc6594)).
+use openssl::sha::{sha1, sha256};
+
+use super::wire;
+use super::{Context, Error, Result, Security};
+
+/// An SSHFP record.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Sshfp {
+    algorithm: u8,
+    fingerprint_type: u8,
+    fingerprint: Vec<u8>,
+}
+
+impl Sshfp {
+    /// Parse the data of an SSHFP record as returned by
+    /// [Answer::data](struct.Answer.html#method.data). Returns `None` if the
+    /// data is malformed.
+    pub fn parse(data: &[u8]) -> Option<Sshfp> {
+        match *data {
+            [algorithm, fingerprint_type, ref rest @ ..] => Some(Sshfp {
+                algorithm,
+                fingerprint_type,
+                fingerprint: rest.to_vec(),
+            }),
+            _ => None,
+        }
+    }
+    /// Returns the key algorithm: 1 for RSA, 2 for DSA, 3 for ECDSA, 4 for Ed25519
+    /// and 6 for Ed448.
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm
+    }
+    /// Returns the fingerprint type: 1 for SHA-1, 2 for SHA-256.
+    pub fn fingerprint_type(&self) -> u8 {
+        self.fingerprint_type
+    }
+    /// Returns the fingerprint.
+    pub fn fingerprint(&self) -> &[u8] {
+        &self.fingerprint
+    }
+    /// Returns true if the record is a fingerprint of an OpenSSH public key blob
+    /// of the record's algorithm.
+    pub fn matches(&self, key_blob: &[u8]) -> bool {
+        if key_algorithm(key_blob) != Some(self.algorithm) {
+            return false;
+        }
+        match self.fingerprint_type {
+            1 => sha1(key_blob)[..] == self.fingerprint[..],
+            2 => sha256(key_blob)[..] == self.fingerprint[..],
+            _ => false,
+        }
+    }
+}
+
+// Returns the SSHFP algorithm of an OpenSSH public key blob, which starts with
+// the key type as a length prefixed string.
+fn key_algorithm(key_blob: &[u8]) -> Option<u8> {
+    let len = wire::read_u32(key_blob, 0)? as usize;
+    let key_type = key_blob.get(4..4 + len)?;
+    match key_type {
+        b"ssh-rsa" => Some(1),
+        b"ssh-dss" => Some(2),
+        b"ecdsa-sha2-nistp256" | b"ecdsa-sha2-nistp384" | b"ecdsa-sha2-nistp521" => Some(3),
+        b"ssh-ed25519" => Some(4),
+        b"ssh-ed448" => Some(6),
+        _ => None,
+    }
+}
+
+/// The SSHFP records of a host, as returned by
+/// [Context::lookup_sshfp](struct.Context.html#method.lookup_sshfp).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupSshfp {
+    records: Vec<Sshfp>,
+    security: Security,
+}
+
+impl LookupSshfp {
+    /// Returns the records found.
+    pub fn records(&self) -> &[Sshfp] {
+        &self.records
+    }
+    /// Returns the outcome of DNSSEC validation of the SSHFP query.
+    pub fn security(&self) -> &Security {
+        &self.security
+    }
+}
+
+impl Context {
+    /// Look up the SSHFP records of a host.
+    pub fn lookup_sshfp(&self, host: &str) -> Result<LookupSshfp> {
+        let answer = self.resolve(host, 44, 1)?.checked()?;
+        Ok(LookupSshfp {
+            records: answer.data().filter_map(Sshfp::parse).collect(),
+            security: answer.security(),
+        })
+    }
+    /// Verify an OpenSSH public key blob, as found base64 encoded in
+    /// `known_hosts` and `authorized_keys` files, against the SSHFP records of a
+    /// host. Returns `Error::Insecure` unless the answer is secure, and otherwise
+    /// whether any record matches.
+    pub fn verify_host_key(&self, host: &str, key_blob: &[u8]) -> Result<bool> {
+        let lookup = self.lookup_sshfp(host)?;
+        if !lookup.security.is_secure() {
+            return Err(Error::Insecure);
+        }
+        Ok(lookup.records.iter().any(|r| r.matches(key_blob)))
+    }
+}
+
+#[test]
+fn test_verify_host_key() {
+    use super::signed_zone::SignedZone;
+    let mut blob = vec![0, 0, 0, 11];
+    blob.extend_from_slice(b"ssh-ed25519");
+    blob.extend_from_slice(&[0, 0, 0, 32]);
+    blob.extend_from_slice(&[7; 32]);
+    let mut zone = SignedZone::new("sshfp.example.");
+    let mut rdata = vec![4, 2];
+    rdata.extend_from_slice(&sha256(&blob));
+    zone.add("host.sshfp.example.", 44, rdata);
+    zone.add("host.sshfp.example.", 44, vec![1, 1, 0xAA]);
+    let ctx = zone.context();
+    let lookup = ctx.lookup_sshfp("host.sshfp.example").unwrap();
+    assert_eq!(lookup.records().len(), 2);
+    assert!(lookup.security().is_secure());
+    assert_eq!(ctx.verify_host_key("host.sshfp.example", &blob), Ok(true));
+    let mut other = blob.clone();
+    other[20] = 8;
+    assert_eq!(ctx.verify_host_key("host.sshfp.example", &other), Ok(false));
+    let ctx = Context::new().unwrap();
+    ctx.zone_add("sshfp.test.", "static").unwrap();
+    ctx.data_add("host.sshfp.test. SSHFP 4 2 00").unwrap();
+    assert_eq!(
+        ctx.verify_host_key("host.sshfp.test", &blob),
+        Err(Error::Insecure)
+    );
+}
@@ -0,0 +1,312 @@
+//! Service binding records ([RFC 9460](https://tools.ietf.org/html/rfc9460)).
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use super::ip::{data_to_ipv4, data_to_ipv6};
+use super::random;
+use super::wire;
+use super::{Context, Result, Security};
+
+// The longest chain of AliasMode records followed.
+const MAX_ALIAS_CHAIN: usize = 8;
+
+// SvcParamKeys understood by this module.
+const KEY_MANDATORY: u16 = 0;
+const KEY_ALPN: u16 = 1;
+const KEY_NO_DEFAULT_ALPN: u16 = 2;
+const KEY_PORT: u16 = 3;
+const KEY_IPV4HINT: u16 = 4;
+const KEY_ECH: u16 = 5;
+const KEY_IPV6HINT: u16 = 6;
+
+/// An SVCB or HTTPS record.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Svcb {
+    priority: u16,
+    target: String,
+    params: Vec<(u16, Vec<u8>)>,
+}
+
+impl Svcb {
+    /// Parse the data of an SVCB or HTTPS record as returned by
+    /// [Answer::data](struct.Answer.html#method.data). Returns `None` if the
+    /// data is malformed.
+    pub fn parse(data: &[u8]) -> Option<Svcb> {
+        let priority = wire::read_u16(data, 0)?;
+        let (target, mut pos) = wire::read_rdata_name(data, 2)?;
+        let mut params: Vec<(u16, Vec<u8>)> = Vec::new();
+        while pos < data.len() {
+            let key = wire::read_u16(data, pos)?;
+            let len = wire::read_u16(data, pos + 2)? as usize;
+            let value = data.get(pos + 4..pos + 4 + len)?;
+            // Keys must be in strictly increasing order.
+            if params.last().is_some_and(|&(last, _)| last >= key) {
+                return None;
+            }
+            params.push((key, value.to_vec()));
+            pos += 4 + len;
+        }
+        Some(Svcb {
+            priority,
+            target,
+            params,
+        })
+    }
+    /// Returns the priority of the record. Zero indicates AliasMode.
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+    /// Returns true if the record is in AliasMode.
+    pub fn is_alias(&self) -> bool {
+        self.priority == 0
+    }
+    /// Returns the target name in presentation format with a trailing dot.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+    /// Returns the raw value of a SvcParam.
+    pub fn param(&self, key: u16) -> Option<&[u8]> {
+        self.params
+            .iter()
+            .find(|&&(k, _)| k == key)
+            .map(|(_, value)| &value[..])
+    }
+    /// Returns the SvcParams as key and raw value pairs.
+    pub fn params(&self) -> &[(u16, Vec<u8>)] {
+        &self.params
+    }
+    /// Returns the keys listed as mandatory.
+    pub fn mandatory(&self) -> Vec<u16> {
+        let value = self.param(KEY_MANDATORY).unwrap_or(&[]);
+        (0..value.len() / 2)
+            .filter_map(|i| wire::read_u16(value, i * 2))
+            .collect()
+    }
+    /// Returns the ALPN protocol identifiers listed in the `alpn` SvcParam.
+    pub fn alpn(&self) -> Vec<Vec<u8>> {
+        let mut value = self.param(KEY_ALPN).unwrap_or(&[]);
+        let mut ids = Vec::new();
+        while let Some((&len, rest)) = value.split_first() {
+            match rest.get(..len as usize) {
+                Some(id) => ids.push(id.to_vec()),
+                None => break,
+            }
+            value = &rest[len as usize..];
+        }
+        ids
+    }
+    /// Returns true if the `no-default-alpn` SvcParam is present.
+    pub fn no_default_alpn(&self) -> bool {
+        self.param(KEY_NO_DEFAULT_ALPN).is_some()
+    }
+    /// Returns the value of the `port` SvcParam.
+    pub fn port(&self) -> Option<u16> {
+        self.param(KEY_PORT)
+            .and_then(|value| wire::read_u16(value, 0))
+    }
+    /// Returns the addresses in the `ipv4hint` SvcParam.
+    pub fn ipv4hint(&self) -> Vec<Ipv4Addr> {
+        let value = self.param(KEY_IPV4HINT).unwrap_or(&[]);
+        value.chunks(4).filter_map(data_to_ipv4).collect()
+    }
+    /// Returns the addresses in the `ipv6hint` SvcParam.
+    pub fn ipv6hint(&self) -> Vec<Ipv6Addr> {
+        let value = self.param(KEY_IPV6HINT).unwrap_or(&[]);
+        value.chunks(16).filter_map(data_to_ipv6).collect()
+    }
+    /// Returns the ECHConfigList in the `ech` SvcParam.
+    pub fn ech(&self) -> Option<&[u8]> {
+        self.param(KEY_ECH)
+    }
+    // Returns true if every mandatory key is understood and present.
+    fn is_compatible(&self) -> bool {
+        self.mandatory()
+            .iter()
+            .all(|&key| key != KEY_MANDATORY && key <= KEY_IPV6HINT && self.param(key).is_some())
+    }
+}
+
+/// An endpoint of an HTTPS service, as returned by
+/// [Context::lookup_https](struct.Context.html#method.lookup_https).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HttpsEndpoint {
+    priority: u16,
+    target: String,
+    port: u16,
+    alpn: Vec<Vec<u8>>,
+    ipv4hint: Vec<Ipv4Addr>,
+    ipv6hint: Vec<Ipv6Addr>,
+    ech: Option<Vec<u8>>,
+}
+
+impl HttpsEndpoint {
+    /// Returns the priority of the endpoint. Lower values are preferred.
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+    /// Returns the name whose addresses should be connected to, in presentation
+    /// format with a trailing dot.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+    /// Returns the port to connect to.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+    /// Returns the ALPN protocol identifiers supported by the endpoint, including
+    /// the default of `http/1.1` unless the record excludes it.
+    pub fn alpn(&self) -> &[Vec<u8>] {
+        &self.alpn
+    }
+    /// Returns the IPv4 address hints.
+    pub fn ipv4hint(&self) -> &[Ipv4Addr] {
+        &self.ipv4hint
+    }
+    /// Returns the IPv6 address hints.
+    pub fn ipv6hint(&self) -> &[Ipv6Addr] {
+        &self.ipv6hint
+    }
+    /// Returns the ECHConfigList to use with the endpoint.
+    pub fn ech(&self) -> Option<&[u8]> {
+        self.ech.as_deref()
+    }
+}
+
+/// The endpoints of an HTTPS service.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LookupHttps {
+    endpoints: Vec<HttpsEndpoint>,
+    security: Security,
+}
+
+impl LookupHttps {
+    /// Returns the endpoints in the order they should be tried. If empty, clients
+    /// should connect to the origin as if no HTTPS records were published.
+    pub fn endpoints(&self) -> &[HttpsEndpoint] {
+        &self.endpoints
+    }
+    /// Returns the outcome of DNSSEC validation. If AliasMode records were
+    /// followed, the outcomes of the queries made are combined as by
+    /// [Security::downgrade](enum.Security.html#method.downgrade).
+    pub fn security(&self) -> &Security {
+        &self.security
+    }
+}
+
+impl Context {
+    /// Look up the HTTPS records of an origin, following AliasMode records, and
+    /// return its endpoints ordered by priority with ties broken randomly.
+    /// Records with mandatory SvcParams that aren't understood are ignored.
+    ///
+    /// For ports other than 443 the records are looked up at
+    /// `_<port>._https.<host>`, and a ServiceMode record there with a target of
+    /// "." refers to the host itself.
+    pub fn lookup_https(&self, host: &str, port: u16) -> Result<LookupHttps> {
+        let mut origin = host.to_ascii_lowercase();
+        if !origin.ends_with('.') {
+            origin.push('.');
+        }
+        let prefixed = if port == 443 {
+            origin.clone()
+        } else {
+            format!("_{}._https.{}", port, origin)
+        };
+        let mut name = prefixed.clone();
+        let mut security = Security::Secure;
+        let mut seen = Vec::new();
+        loop {
+            let answer = self.resolve(&name, 65, 1)?.checked()?;
+            security.downgrade(answer.security());
+            let mut owner = answer.canonname().unwrap_or(&name).to_owned();
+            if !owner.ends_with('.') {
+                owner.push('.');
+            }
+            let records: Vec<_> = answer.data().filter_map(Svcb::parse).collect();
+            let alias = records.iter().find(|r| r.is_alias());
+            if let Some(alias) = alias {
+                seen.push(owner.trim_end_matches('.').to_ascii_lowercase());
+                let target = alias.target.trim_end_matches('.').to_ascii_lowercase();
+                if alias.target == "." || seen.len() > MAX_ALIAS_CHAIN || seen.contains(&target) {
+                    return Ok(LookupHttps {
+                        endpoints: Vec::new(),
+                        security,
+                    });
+                }
+                name = alias.target.clone();
+                continue;
+            }
+            let effective = if owner.eq_ignore_ascii_case(&prefixed) {
+                &origin
+            } else {
+                &owner
+            };
+            let mut endpoints: Vec<_> = records
+                .iter()
+                .filter(|r| r.is_compatible())
+                .map(|r| {
+                    let mut alpn = r.alpn();
+                    if !r.no_default_alpn() && !alpn.iter().any(|id| id == b"http/1.1") {
+                        alpn.push(b"http/1.1".to_vec());
+                    }
+                    HttpsEndpoint {
+                        priority: r.priority,
+                        target: if r.target == "." {
+                            effective.clone()
+                        } else {
+                            r.target.clone()
+                        },
+                        port: r.port().unwrap_or(port),
+                        alpn,
+                        ipv4hint: r.ipv4hint(),
+                        ipv6hint: r.ipv6hint(),
+                        ech: r.ech().map(<[u8]>::to_vec),
+                    }
+                })
+                .collect();
+            random::shuffle(&mut endpoints);
+            endpoints.sort_by_key(|e| e.priority);
+            return Ok(LookupHttps {
+                endpoints,
+                security,
+            });
+        }
+    }
+}
+
+#[test]
+fn test_lookup_https() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    ctx.zone_add("svcb.test.", "static").unwrap();
+    ctx.data_add("alias.svcb.test. HTTPS 0 www.svcb.test.")
+        .unwrap();
+    ctx.data_add("www.svcb.test. HTTPS 2 . alpn=h2 ipv4hint=192.0.2.2")
+        .unwrap();
+    ctx.data_add("www.svcb.test. HTTPS 1 cdn.svcb.test. alpn=h3,h2 port=8443 ipv6hint=2001:db8::1 no-default-alpn")
+        .unwrap();
+    ctx.data_add("www.svcb.test. HTTPS 1 new.svcb.test. key65000=x mandatory=key65000")
+        .unwrap();
+    ctx.data_add("loop.svcb.test. HTTPS 0 loop.svcb.test.")
+        .unwrap();
+    ctx.data_add("_8080._https.www.svcb.test. HTTPS 1 . alpn=h2")
+        .unwrap();
+    let https = ctx.lookup_https("alias.svcb.test", 443).unwrap();
+    let endpoints = https.endpoints();
+    assert_eq!(endpoints.len(), 2);
+    assert_eq!(endpoints[0].target(), "cdn.svcb.test.");
+    assert_eq!(endpoints[0].port(), 8443);
+    assert_eq!(endpoints[0].alpn(), [b"h3".to_vec(), b"h2".to_vec()]);
+    assert_eq!(
+        endpoints[0].ipv6hint(),
+        ["2001:db8::1".parse::<Ipv6Addr>().unwrap()]
+    );
+    assert_eq!(endpoints[1].target(), "www.svcb.test.");
+    assert_eq!(endpoints[1].port(), 443);
+    assert_eq!(endpoints[1].alpn(), [b"h2".to_vec(), b"http/1.1".to_vec()]);
+    assert_eq!(endpoints[1].ipv4hint(), [Ipv4Addr::new(192, 0, 2, 2)]);
+    let https = ctx.lookup_https("loop.svcb.test", 443).unwrap();
+    assert!(https.endpoints().is_empty());
+    let https = ctx.lookup_https("www.svcb.test", 8080).unwrap();
+    assert_eq!(https.endpoints().len(), 1);
+    assert_eq!(https.endpoints()[0].target(), "www.svcb.test.");
+    assert_eq!(https.endpoints()[0].port(), 8080);
+}
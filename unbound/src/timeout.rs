@@ -0,0 +1,242 @@
+//! Timeouts and deadlines.
+use std::cmp;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use libc::{self, c_int};
+use sys;
+
+use super::{Answer, AsyncID, Context, Error, Result};
+
+// ub_ctx_err::UB_SYSERR
+const UB_SYSERR: c_int = -2;
+
+// Upper bound on how long to block on `fd` while waiting for a specific result.
+// Another thread may process the result, in which case `fd` won't become readable.
+const POLL_INTERVAL_MS: u64 = 10;
+
+// How long the lookups built on several concurrent queries wait for each one,
+// unless set with `set_lookup_timeout`.
+const DEFAULT_LOOKUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl Context {
+    /// Resolve and validate a query, giving up with `Error::TimedOut` if no answer
+    /// is available within `timeout`.
+    ///
+    /// The query is made asynchronously and the `Context` is processed until it
+    /// completes, so other queries' callbacks may be called in the meantime.
+    pub fn resolve_with_timeout(
+        &self,
+        name: &str,
+        rrtype: u16,
+        class: u16,
+        timeout: Duration,
+    ) -> Result<Answer> {
+        let deadline = Instant::now() + timeout;
+        let (tx, rx) = mpsc::channel();
+        self.resolve_async_with_deadline(name, rrtype, class, deadline, move |_, result| {
+            let _ = tx.send(result);
+        })?;
+        self.process_until(|| match rx.try_recv() {
+            Ok(result) => Some(result),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err(Error::Cancelled)),
+        })?
+    }
+    /// Resolve and validate a query asynchronously. If no answer is available by
+    /// `deadline` the query is cancelled and `callback` is called with
+    /// `Error::TimedOut`.
+    ///
+    /// Deadlines are enforced by `process`, `process_timeout` and `wait_timeout`.
+    pub fn resolve_async_with_deadline<C>(
+        &self,
+        name: &str,
+        rrtype: u16,
+        class: u16,
+        deadline: Instant,
+        callback: C,
+    ) -> Result<AsyncID>
+    where
+        C: Fn(AsyncID, Result<Answer>) + 'static,
+    {
+        self.resolve_async_imp(name, rrtype, class, Some(deadline), callback)
+    }
+    /// Set how long lookups that make several queries concurrently, such as
+    /// [Context::lookup_ip](struct.Context.html#method.lookup_ip), wait for each
+    /// query before it fails with `Error::TimedOut`. Defaults to 30 seconds.
+    pub fn set_lookup_timeout(&self, timeout: Duration) {
+        self.protected
+            .lock()
+            .expect("set_lookup_timeout acquire protected")
+            .lookup_timeout = Some(timeout);
+    }
+    // Returns the deadline for queries made by a lookup starting now.
+    pub(crate) fn lookup_deadline(&self) -> Instant {
+        let timeout = self
+            .protected
+            .lock()
+            .expect("lookup_deadline acquire protected")
+            .lookup_timeout
+            .unwrap_or(DEFAULT_LOOKUP_TIMEOUT);
+        Instant::now() + timeout
+    }
+    /// Returns the earliest deadline of any outstanding asynchronous query.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.protected
+            .lock()
+            .expect("next_deadline acquire protected")
+            .callbacks
+            .iter()
+            .filter_map(|cb| cb.deadline)
+            .min()
+    }
+    /// Wait up to `timeout` for `fd` to become readable and process any results.
+    /// Returns early if a query's deadline passes first.
+    pub fn process_timeout(&self, timeout: Duration) -> Result<()> {
+        let timeout = match self.next_deadline() {
+            Some(deadline) => cmp::min(timeout, deadline.saturating_duration_since(Instant::now())),
+            None => timeout,
+        };
+        if self.poll_fd(timeout)? {
+            self.process()
+        } else {
+            self.expire_deadlines();
+            Ok(())
+        }
+    }
+    /// Waits up to `timeout` for outstanding queries to complete, processing
+    /// results as they become available. Returns `Error::TimedOut` if queries
+    /// remain outstanding after `timeout`.
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        while self.have_waiting() {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::TimedOut);
+            }
+            self.process_timeout(deadline - now)?;
+        }
+        Ok(())
+    }
+    /// Processes the `Context` until `f` returns a value. The caller must ensure
+    /// `f` eventually returns a value, typically by bounding queries with
+    /// deadlines.
+    pub(crate) fn process_until<T, F>(&self, mut f: F) -> Result<T>
+    where
+        F: FnMut() -> Option<T>,
+    {
+        loop {
+            if let Some(t) = f() {
+                return Ok(t);
+            }
+            self.process_timeout(Duration::from_millis(POLL_INTERVAL_MS))?;
+        }
+    }
+    // Cancel queries whose deadline has passed and notify their callbacks.
+    pub(crate) fn expire_deadlines(&self) {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        {
+            let mut p = self
+                .protected
+                .lock()
+                .expect("expire_deadlines acquire protected");
+            let mut i = 0;
+            while i < p.callbacks.len() {
+                match p.callbacks[i].deadline {
+                    Some(deadline) if deadline <= now => {
+                        let cb = p.callbacks.swap_remove(i);
+                        unsafe { sys::ub_cancel(self.ub_ctx, cb.ub_id) };
+                        expired.push(cb);
+                    }
+                    _ => i += 1,
+                }
+            }
+            if !expired.is_empty() {
+                p.adjust_capacity();
+            }
+        }
+        for cb in expired {
+            (cb.f)(cb.async_id, Err(Error::TimedOut));
+        }
+    }
+    fn poll_fd(&self, timeout: Duration) -> Result<bool> {
+        let ms = timeout
+            .as_secs()
+            .saturating_mul(1000)
+            .saturating_add((u64::from(timeout.subsec_nanos()) + 999_999) / 1_000_000);
+        let ms = cmp::min(ms, c_int::MAX as u64) as c_int;
+        let mut pfd = libc::pollfd {
+            fd: self.fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut pfd, 1, ms) } {
+            n if n > 0 => Ok(true),
+            0 => Ok(false),
+            _ => {
+                if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                    Ok(false)
+                } else {
+                    Err(Error::UB(UB_SYSERR))
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_resolve_with_timeout() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    let answer = ctx
+        .resolve_with_timeout("localhost", 1, 1, Duration::from_secs(5))
+        .unwrap();
+    assert!(answer.havedata());
+}
+
+#[test]
+fn test_deadline_expires() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let id = ctx
+        .resolve_async_with_deadline("localhost", 1, 1, Instant::now(), move |id, r| {
+            tx.send((id, r.err())).unwrap()
+        })
+        .unwrap();
+    assert!(ctx.next_deadline().is_some());
+    ctx.expire_deadlines();
+    match rx.try_recv().unwrap() {
+        (i, Some(Error::TimedOut)) => assert_eq!(i, id),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(!ctx.have_waiting());
+    assert!(ctx.wait_timeout(Duration::from_secs(1)).is_ok());
+}
+
+#[test]
+fn test_resolve_timed_out() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    // TEST-NET-1 (RFC 5737) shouldn't answer.
+    ctx.set_fwd4(std::net::Ipv4Addr::new(192, 0, 2, 1)).unwrap();
+    match ctx.resolve_with_timeout("example.com", 1, 1, Duration::from_millis(100)) {
+        Err(Error::TimedOut) => (),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(!ctx.have_waiting());
+}
+
+#[test]
+fn test_lookup_timeout() {
+    let ctx = Context::new().unwrap();
+    ctx.async_via_thread().unwrap();
+    ctx.set_fwd4(std::net::Ipv4Addr::new(192, 0, 2, 1)).unwrap();
+    ctx.set_lookup_timeout(Duration::from_millis(100));
+    match ctx.lookup_ip("example.com") {
+        Err(Error::TimedOut) => (),
+        other => panic!("unexpected {:?}", other),
+    }
+    assert!(!ctx.have_waiting());
+}
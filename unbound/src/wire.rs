@@ -0,0 +1,280 @@
+//! Decoding of DNS wire format messages and record data.
+use super::Answer;
+
+// Limits how many compression pointers are followed when reading a name.
+const MAX_POINTERS: usize = 64;
+// Maximum length of a name in wire format.
+const MAX_NAME_LEN: usize = 255;
+
+/// A message header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Header {
+    pub(crate) id: u16,
+    pub(crate) qr: bool,
+    pub(crate) opcode: u8,
+    pub(crate) aa: bool,
+    pub(crate) tc: bool,
+    pub(crate) rd: bool,
+    pub(crate) ra: bool,
+    pub(crate) ad: bool,
+    pub(crate) cd: bool,
+    pub(crate) rcode: u8,
+    pub(crate) qdcount: u16,
+    pub(crate) ancount: u16,
+    pub(crate) nscount: u16,
+    pub(crate) arcount: u16,
+}
+
+/// An entry in a message's question section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Question {
+    pub(crate) name: String,
+    pub(crate) qtype: u16,
+    pub(crate) qclass: u16,
+}
+
+/// A resource record, borrowing its data from the message it was read from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Record<'a> {
+    pub(crate) name: String,
+    pub(crate) rrtype: u16,
+    pub(crate) class: u16,
+    pub(crate) ttl: u32,
+    msg: &'a [u8],
+    start: usize,
+    end: usize,
+}
+
+impl<'a> Record<'a> {
+    /// Returns the record's data.
+    pub(crate) fn rdata(&self) -> &'a [u8] {
+        &self.msg[self.start..self.end]
+    }
+    /// Returns the message the record was read from, truncated at the end of the
+    /// record's data, and the offset of the data. Names within the data may be
+    /// compressed.
+    pub(crate) fn rdata_in_message(&self) -> (&'a [u8], usize) {
+        (&self.msg[..self.end], self.start)
+    }
+}
+
+/// A decoded message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Message<'a> {
+    pub(crate) header: Header,
+    pub(crate) question: Vec<Question>,
+    pub(crate) answer: Vec<Record<'a>>,
+    pub(crate) authority: Vec<Record<'a>>,
+    pub(crate) additional: Vec<Record<'a>>,
+}
+
+impl<'a> Message<'a> {
+    /// Decode `msg`. Returns `None` if it is malformed.
+    pub(crate) fn parse(msg: &'a [u8]) -> Option<Message<'a>> {
+        let flags = read_u16(msg, 2)?;
+        let header = Header {
+            id: read_u16(msg, 0)?,
+            qr: flags & 0x8000 != 0,
+            opcode: ((flags >> 11) & 0xF) as u8,
+            aa: flags & 0x0400 != 0,
+            tc: flags & 0x0200 != 0,
+            rd: flags & 0x0100 != 0,
+            ra: flags & 0x0080 != 0,
+            ad: flags & 0x0020 != 0,
+            cd: flags & 0x0010 != 0,
+            rcode: (flags & 0xF) as u8,
+            qdcount: read_u16(msg, 4)?,
+            ancount: read_u16(msg, 6)?,
+            nscount: read_u16(msg, 8)?,
+            arcount: read_u16(msg, 10)?,
+        };
+        let mut pos = 12;
+        let mut question = Vec::new();
+        for _ in 0..header.qdcount {
+            let (name, next) = read_name(msg, pos)?;
+            question.push(Question {
+                name,
+                qtype: read_u16(msg, next)?,
+                qclass: read_u16(msg, next + 2)?,
+            });
+            pos = next + 4;
+        }
+        let mut sections = [Vec::new(), Vec::new(), Vec::new()];
+        let counts = [header.ancount, header.nscount, header.arcount];
+        for (section, &count) in sections.iter_mut().zip(counts.iter()) {
+            for _ in 0..count {
+                let (name, next) = read_name(msg, pos)?;
+                let rdlen = read_u16(msg, next + 8)? as usize;
+                let start = next + 10;
+                let end = start + rdlen;
+                if end > msg.len() {
+                    return None;
+                }
+                section.push(Record {
+                    name,
+                    rrtype: read_u16(msg, next)?,
+                    class: read_u16(msg, next + 2)?,
+                    ttl: read_u32(msg, next + 4)?,
+                    msg,
+                    start,
+                    end,
+                });
+                pos = end;
+            }
+        }
+        let [answer, authority, additional] = sections;
+        Some(Message {
+            header,
+            question,
+            answer,
+            authority,
+            additional,
+        })
+    }
+}
+
+/// Read a big-endian `u16` at `pos`.
+pub(crate) fn read_u16(buf: &[u8], pos: usize) -> Option<u16> {
+    let b = buf.get(pos..pos + 2)?;
+    Some(u16::from(b[0]) << 8 | u16::from(b[1]))
+}
+
+/// Read a big-endian `u32` at `pos`.
+pub(crate) fn read_u32(buf: &[u8], pos: usize) -> Option<u32> {
+    let hi = read_u16(buf, pos)?;
+    let lo = read_u16(buf, pos + 2)?;
+    Some(u32::from(hi) << 16 | u32::from(lo))
+}
+
+/// Read a possibly compressed name at `pos` in `msg`, returning it in presentation
+/// format with a trailing dot along with the offset following it.
+pub(crate) fn read_name(msg: &[u8], pos: usize) -> Option<(String, usize)> {
+    read_name_imp(msg, pos, true)
+}
+
+/// Read an uncompressed name from record data as returned by libunbound.
+pub(crate) fn read_rdata_name(data: &[u8], pos: usize) -> Option<(String, usize)> {
+    read_name_imp(data, pos, false)
+}
+
+/// Read the character-strings of TXT record data and concatenate them.
+pub(crate) fn read_txt(data: &[u8]) -> Option<Vec<u8>> {
+    let mut text = Vec::with_capacity(data.len());
+    let mut rest = data;
+    while let Some((&len, tail)) = rest.split_first() {
+        text.extend_from_slice(tail.get(..len as usize)?);
+        rest = &tail[len as usize..];
+    }
+    Some(text)
+}
+
+/// Parse a tag-value list as used by DKIM and DMARC records, returning the tags
+/// in order. Returns `None` if a tag is malformed or repeated.
+pub(crate) fn parse_tags(text: &str) -> Option<Vec<(&str, &str)>> {
+    let mut tags: Vec<(&str, &str)> = Vec::new();
+    for spec in text.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+        let (name, value) = spec.split_once('=')?;
+        let (name, value) = (name.trim(), value.trim());
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_');
+        if !valid || tags.iter().any(|&(n, _)| n == name) {
+            return None;
+        }
+        tags.push((name, value));
+    }
+    Some(tags)
+}
+
+/// Returns the text of the TXT records in an answer, with the character-strings
+/// of each record concatenated. Records that aren't UTF-8 are skipped.
+pub(crate) fn answer_txt(answer: &Answer) -> Vec<String> {
+    answer
+        .data()
+        .filter_map(read_txt)
+        .filter_map(|text| String::from_utf8(text).ok())
+        .collect()
+}
+
+fn read_name_imp(msg: &[u8], pos: usize, compressed: bool) -> Option<(String, usize)> {
+    let mut name = String::new();
+    let mut pos = pos;
+    let mut next = None;
+    let mut pointers = 0;
+    let mut len = 1;
+    loop {
+        let label_len = *msg.get(pos)? as usize;
+        match label_len & 0xC0 {
+            0x00 if label_len == 0 => break,
+            0x00 => {
+                let label = msg.get(pos + 1..pos + 1 + label_len)?;
+                len += label_len + 1;
+                if len > MAX_NAME_LEN {
+                    return None;
+                }
+                push_label(&mut name, label);
+                name.push('.');
+                pos += 1 + label_len;
+            }
+            0xC0 if compressed => {
+                pointers += 1;
+                if pointers > MAX_POINTERS {
+                    return None;
+                }
+                let target = (read_u16(msg, pos)? & 0x3FFF) as usize;
+                if next.is_none() {
+                    next = Some(pos + 2);
+                }
+                pos = target;
+            }
+            _ => return None,
+        }
+    }
+    if name.is_empty() {
+        name.push('.');
+    }
+    Some((name, next.unwrap_or(pos + 1)))
+}
+
+fn push_label(name: &mut String, label: &[u8]) {
+    for &b in label {
+        match b {
+            b'.' | b'\\' | b'"' | b';' | b'(' | b')' | b'@' | b'$' => {
+                name.push('\\');
+                name.push(b as char);
+            }
+            0x21..=0x7E => name.push(b as char),
+            _ => name.push_str(&format!("\\{:03}", b)),
+        }
+    }
+}
+
+/// Encode `bytes` as upper case hexadecimal.
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    const DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for &b in bytes {
+        s.push(DIGITS[(b >> 4) as usize] as char);
+        s.push(DIGITS[(b & 0xF) as usize] as char);
+    }
+    s
+}
+
+/// Decode hexadecimal, ignoring case.
+#[cfg(feature = "serde")]
+pub(crate) fn unhex(s: &str) -> Option<Vec<u8>> {
+    fn digit(b: u8) -> Option<u8> {
+        match b {
+            b'0'..=b'9' => Some(b - b'0'),
+            b'a'..=b'f' => Some(b - b'a' + 10),
+            b'A'..=b'F' => Some(b - b'A' + 10),
+            _ => None,
+        }
+    }
+    let s = s.as_bytes();
+    if s.len() & 1 == 1 {
+        return None;
+    }
+    s.chunks(2)
+        .map(|pair| Some(digit(pair[0])? << 4 | digit(pair[1])?))
+        .collect()
+}